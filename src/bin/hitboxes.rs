@@ -0,0 +1,91 @@
+use anyhow::{anyhow, Result};
+use croftsoft_walk_the_dog::engine::{Cell, Hitboxes, Sheet, SheetRect};
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::path::Path;
+
+const HITBOXES_NAME: &str = "rhb_hitboxes.json";
+const SHEET_NAME: &str = "rhb.json";
+const STATIC_DIR: &str = "static";
+const WIDTH_OFFSET: i16 = 28;
+const X_OFFSET: i16 = 18;
+const Y_OFFSET: i16 = 14;
+
+fn main() -> Result<()> {
+  let static_dir = std::env::args().nth(1).unwrap_or_else(|| STATIC_DIR.into());
+  let static_dir = Path::new(&static_dir);
+  let sheet: Sheet = load_json(static_dir, SHEET_NAME)?;
+  let mut hitboxes: Hitboxes = if static_dir.join(HITBOXES_NAME).exists() {
+    load_json(static_dir, HITBOXES_NAME)?
+  } else {
+    Hitboxes::default()
+  };
+  hitboxes.frames.retain(|name, _hitboxes| {
+    let known = sheet.frames.contains_key(name);
+    if !known {
+      println!("Removed {}", name);
+    }
+    known
+  });
+  let mut names: Vec<&String> = sheet.frames.keys().collect();
+  names.sort();
+  names.into_iter().for_each(|name| {
+    let frame_hitboxes = hitboxes.frames.entry(name.clone()).or_default();
+    if frame_hitboxes.is_empty() {
+      println!("Added {}", name);
+      frame_hitboxes.push(default_hitbox(&sheet.frames[name]));
+    }
+  });
+  fs::write(static_dir.join(HITBOXES_NAME), to_json(&hitboxes)?)?;
+  println!(
+    "Wrote {} frames to {}",
+    hitboxes.frames.len(),
+    HITBOXES_NAME
+  );
+  Ok(())
+}
+
+fn default_hitbox(cell: &Cell) -> SheetRect {
+  SheetRect {
+    x: cell.sprite_source_size.x + X_OFFSET,
+    y: cell.sprite_source_size.y + Y_OFFSET,
+    w: cell.frame.w - WIDTH_OFFSET,
+    h: cell.frame.h - Y_OFFSET,
+  }
+}
+
+fn load_json<T: DeserializeOwned>(
+  static_dir: &Path,
+  name: &str,
+) -> Result<T> {
+  let json = fs::read_to_string(static_dir.join(name))?;
+  serde_json::from_str(&json)
+    .map_err(|err| anyhow!("Could not parse {} {:#?}", name, err))
+}
+
+fn to_json(hitboxes: &Hitboxes) -> Result<String> {
+  let frames = hitboxes
+    .frames
+    .iter()
+    .map(|(name, frame_hitboxes)| {
+      let rects: Vec<String> = frame_hitboxes
+        .iter()
+        .map(|hitbox| {
+          format!(
+            "{{\"x\": {}, \"y\": {}, \"w\": {}, \"h\": {}}}",
+            hitbox.x, hitbox.y, hitbox.w, hitbox.h
+          )
+        })
+        .collect();
+      Ok(format!(
+        "    {}: [{}]",
+        serde_json::to_string(name)?,
+        rects.join(", ")
+      ))
+    })
+    .collect::<Result<Vec<String>>>()?;
+  Ok(format!(
+    "{{\n  \"frames\": {{\n{}\n  }}\n}}\n",
+    frames.join(",\n")
+  ))
+}
@@ -0,0 +1,273 @@
+use anyhow::{anyhow, Result};
+use croftsoft_walk_the_dog::engine::{ImageElement, Sheet, SpriteSheet};
+use croftsoft_walk_the_dog::leaderboard::{
+  self, Assets, Recording, Score, Submission, DEFAULT_LIVES, TOP_SCORES,
+};
+use serde::de::DeserializeOwned;
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::rc::Rc;
+
+const ADDRESS: &str = "127.0.0.1:8081";
+const MAX_BODY_LENGTH: usize = 1 << 20;
+const MAX_NAME_LENGTH: usize = 16;
+const MAX_TICKS: u32 = 60 * 60 * 60;
+const PNG_HEADER_LENGTH: usize = 24;
+const SCORES_PATH: &str = "/scores";
+const STATIC_DIR: &str = "static";
+
+struct Request {
+  body: String,
+  method: String,
+  path: String,
+}
+
+struct Response {
+  body: String,
+  status: &'static str,
+}
+
+impl Response {
+  fn json(scores: &[Score]) -> Self {
+    Response {
+      body: serde_json::to_string(scores).unwrap_or_default(),
+      status: "200 OK",
+    }
+  }
+
+  fn status(status: &'static str) -> Self {
+    Response {
+      body: String::new(),
+      status,
+    }
+  }
+}
+
+fn main() -> Result<()> {
+  let static_dir = std::env::args().nth(1).unwrap_or_else(|| STATIC_DIR.into());
+  let assets = load_assets(Path::new(&static_dir))?;
+  let listener = TcpListener::bind(ADDRESS)?;
+  println!("Leaderboard listening on http://{}{}", ADDRESS, SCORES_PATH);
+  let mut scores: Vec<Score> = Vec::new();
+  let mut submitted: HashSet<u64> = HashSet::new();
+  for stream in listener.incoming() {
+    let mut stream = match stream {
+      Ok(stream) => stream,
+      Err(err) => {
+        eprintln!("Error accepting connection {:#?}", err);
+        continue;
+      },
+    };
+    let response = match read_request(&mut stream) {
+      Ok(request) => {
+        handle_request(&request, &assets, &mut scores, &mut submitted)
+      },
+      Err(err) => {
+        eprintln!("Error reading request {:#?}", err);
+        Response::status("400 Bad Request")
+      },
+    };
+    if let Err(err) = write_response(&mut stream, &response) {
+      eprintln!("Error writing response {:#?}", err);
+    }
+  }
+  Ok(())
+}
+
+fn handle_request(
+  request: &Request,
+  assets: &Assets,
+  scores: &mut Vec<Score>,
+  submitted: &mut HashSet<u64>,
+) -> Response {
+  match (request.method.as_str(), request.path.as_str()) {
+    ("GET", SCORES_PATH) => Response::json(scores),
+    ("OPTIONS", SCORES_PATH) => Response::status("204 No Content"),
+    ("POST", SCORES_PATH) => submit_score(request, assets, scores, submitted),
+    _ => Response::status("404 Not Found"),
+  }
+}
+
+fn load_assets(static_dir: &Path) -> Result<Assets> {
+  let assets = Assets {
+    background: load_image(static_dir, "BG.png")?,
+    dog_image: load_image(static_dir, "dog.png")?,
+    dog_sheet: load_json(static_dir, "dog.json")?,
+    pickups: Rc::new(SpriteSheet::new(
+      load_image(static_dir, "pickups.png")?,
+      load_json(static_dir, "pickups.json")?,
+    )),
+    rhb_image: load_image(static_dir, "rhb.png")?,
+    rhb_sheet: load_json::<Sheet>(static_dir, "rhb.json")?
+      .with_hitboxes(load_json(static_dir, "rhb_hitboxes.json")?),
+    segments: load_json(static_dir, "segments.json")?,
+    stone: load_image(static_dir, "Stone.png")?,
+    tiles: Rc::new(SpriteSheet::new(
+      load_image(static_dir, "tiles.png")?,
+      load_json(static_dir, "tiles.json")?,
+    )),
+  };
+  assets.validate()?;
+  Ok(assets)
+}
+
+fn load_image(
+  static_dir: &Path,
+  name: &str,
+) -> Result<ImageElement> {
+  let mut header = [0; PNG_HEADER_LENGTH];
+  File::open(static_dir.join(name))?.read_exact(&mut header)?;
+  if &header[12..16] != b"IHDR" {
+    return Err(anyhow!("{} is not a PNG", name));
+  }
+  let width = u32::from_be_bytes([
+    header[16], header[17], header[18], header[19],
+  ]);
+  let height = u32::from_be_bytes([
+    header[20], header[21], header[22], header[23],
+  ]);
+  Ok(ImageElement::headless(
+    name,
+    i16::try_from(width)?,
+    i16::try_from(height)?,
+  ))
+}
+
+fn load_json<T: DeserializeOwned>(
+  static_dir: &Path,
+  name: &str,
+) -> Result<T> {
+  let json = fs::read_to_string(static_dir.join(name))?;
+  serde_json::from_str(&json)
+    .map_err(|err| anyhow!("Could not parse {} {:#?}", name, err))
+}
+
+fn read_request(stream: &mut TcpStream) -> Result<Request> {
+  let mut reader = BufReader::new(stream);
+  let mut request_line = String::new();
+  reader.read_line(&mut request_line)?;
+  let mut parts = request_line.split_whitespace();
+  let method = parts
+    .next()
+    .ok_or_else(|| anyhow!("No method"))?
+    .to_string();
+  let target = parts.next().ok_or_else(|| anyhow!("No path"))?;
+  let path = target.split('?').next().unwrap_or_default().to_string();
+  let mut content_length = 0;
+  loop {
+    let mut header = String::new();
+    reader.read_line(&mut header)?;
+    let header = header.trim_end();
+    if header.is_empty() {
+      break;
+    }
+    if let Some((name, value)) = header.split_once(':') {
+      if name.eq_ignore_ascii_case("Content-Length") {
+        content_length = value.trim().parse()?;
+      }
+    }
+  }
+  if content_length > MAX_BODY_LENGTH {
+    return Err(anyhow!("Body of {} bytes is too large", content_length));
+  }
+  let mut body = vec![0; content_length];
+  reader.read_exact(&mut body)?;
+  Ok(Request {
+    body: String::from_utf8(body)?,
+    method,
+    path,
+  })
+}
+
+fn recording_hash(recording: &Recording) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  recording.seed.hash(&mut hasher);
+  serde_json::to_string(&recording.input_log)
+    .unwrap_or_default()
+    .hash(&mut hasher);
+  hasher.finish()
+}
+
+fn submit_score(
+  request: &Request,
+  assets: &Assets,
+  scores: &mut Vec<Score>,
+  submitted: &mut HashSet<u64>,
+) -> Response {
+  let submission: Submission = match serde_json::from_str(&request.body) {
+    Ok(submission) => submission,
+    Err(err) => {
+      eprintln!("Error parsing submission {:#?}", err);
+      return Response::status("400 Bad Request");
+    },
+  };
+  if submission.recording.lives > DEFAULT_LIVES {
+    println!(
+      "Rejected {} lives from {}",
+      submission.recording.lives, submission.name
+    );
+    return Response::status("422 Unprocessable Entity");
+  }
+  let hash = recording_hash(&submission.recording);
+  if submitted.contains(&hash) {
+    println!("Rejected duplicate run from {}", submission.name);
+    return Response::status("409 Conflict");
+  }
+  let result =
+    leaderboard::simulate(assets.clone(), submission.recording, MAX_TICKS);
+  if result != submission.result {
+    println!(
+      "Rejected {:?} from {}, replay gives {:?}",
+      submission.result, submission.name, result
+    );
+    return Response::status("422 Unprocessable Entity");
+  }
+  submitted.insert(hash);
+  let name: String = submission
+    .name
+    .trim()
+    .chars()
+    .take(MAX_NAME_LENGTH)
+    .collect();
+  scores.push(Score {
+    distance: result.distance,
+    name: if name.is_empty() {
+      "Anonymous".into()
+    } else {
+      name
+    },
+    score: result.score,
+  });
+  scores.sort_by_key(|score| Reverse(score.score));
+  scores.truncate(TOP_SCORES);
+  Response::json(scores)
+}
+
+fn write_response(
+  stream: &mut TcpStream,
+  response: &Response,
+) -> Result<()> {
+  write!(
+    stream,
+    "HTTP/1.1 {}\r\n\
+    Access-Control-Allow-Headers: Content-Type\r\n\
+    Access-Control-Allow-Methods: GET, POST, OPTIONS\r\n\
+    Access-Control-Allow-Origin: *\r\n\
+    Connection: close\r\n\
+    Content-Length: {}\r\n\
+    Content-Type: application/json\r\n\
+    \r\n\
+    {}",
+    response.status,
+    response.body.len(),
+    response.body
+  )?;
+  stream.flush()?;
+  Ok(())
+}
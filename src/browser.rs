@@ -1,6 +1,7 @@
 use anyhow::{anyhow, Result};
 use futures::Future;
 use js_sys::ArrayBuffer;
+use std::fmt;
 use wasm_bindgen::{
   closure::{WasmClosure, WasmClosureFnOnce},
   prelude::Closure,
@@ -8,19 +9,55 @@ use wasm_bindgen::{
 };
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{
-  CanvasRenderingContext2d, Document, Element, HtmlCanvasElement, HtmlElement,
-  HtmlImageElement, Response, Window,
+  CanvasRenderingContext2d, Document, Element, EventTarget, Gamepad, Headers,
+  HtmlCanvasElement, HtmlElement, HtmlImageElement, HtmlInputElement,
+  RequestInit, Response, UrlSearchParams, Window,
 };
 
 macro_rules! log {
   ( $( $t:tt )* ) => {
-    web_sys::console::log_1(&format!( $( $t )*
-    ).into());
+    {
+      #[cfg(target_arch = "wasm32")]
+      web_sys::console::log_1(&format!( $( $t )* ).into());
+      #[cfg(not(target_arch = "wasm32"))]
+      let _message = format!( $( $t )* );
+    }
   }
 }
 
+pub mod storage;
+
 pub type LoopClosure = Closure<dyn FnMut(f64)>;
 
+#[derive(Debug)]
+pub struct HttpStatus(pub u16);
+
+impl fmt::Display for HttpStatus {
+  fn fmt(
+    &self,
+    f: &mut fmt::Formatter<'_>,
+  ) -> fmt::Result {
+    write!(f, "status {}", self.0)
+  }
+}
+
+impl std::error::Error for HttpStatus {}
+
+pub fn add_event_listener<T: WasmClosure + ?Sized>(
+  target: &EventTarget,
+  event_type: &str,
+  listener: &Closure<T>,
+) -> Result<()> {
+  target
+    .add_event_listener_with_callback(
+      event_type,
+      listener.as_ref().unchecked_ref(),
+    )
+    .map_err(|err| {
+      anyhow!("Could not add {} event listener {:#?}", event_type, err)
+    })
+}
+
 pub fn canvas() -> Result<HtmlCanvasElement> {
   document()?
     .get_element_by_id("canvas")
@@ -74,13 +111,32 @@ pub fn draw_ui(html: &str) -> Result<()> {
 
 pub async fn fetch_json(json_path: &str) -> Result<JsValue> {
   let resp: Response = fetch_response(json_path).await?;
-  JsFuture::from(
-    resp
-      .json()
-      .map_err(|err| anyhow!("Could not get JSON from response {:#?}", err))?,
-  )
-  .await
-  .map_err(|err| anyhow!("error fetching JSON {:#?}", err))
+  response_json(resp).await
+}
+
+pub async fn fetch_json_with_body(
+  resource: &str,
+  method: &str,
+  body: &JsValue,
+) -> Result<JsValue> {
+  let headers = Headers::new()
+    .map_err(|err| anyhow!("Could not create headers {:#?}", err))?;
+  headers
+    .set("Content-Type", "application/json")
+    .map_err(|err| anyhow!("Could not set content type {:#?}", err))?;
+  let mut init = RequestInit::new();
+  init
+    .method(method)
+    .headers(&headers)
+    .body(Some(&JsValue::from_str(&stringify_json(body)?)));
+  let resp: Response = fetch_response_with_init(resource, &init).await?;
+  if !resp.ok() {
+    return Err(
+      anyhow::Error::new(HttpStatus(resp.status()))
+        .context(format!("{} {} failed", method, resource)),
+    );
+  }
+  response_json(resp).await
 }
 
 pub async fn fetch_array_buffer(resource: &str) -> Result<ArrayBuffer> {
@@ -106,12 +162,35 @@ pub async fn fetch_response(resource: &str) -> Result<Response> {
     .map_err(|err| anyhow!("error converting fetch to Response {:#?}", err))
 }
 
+pub async fn fetch_response_with_init(
+  resource: &str,
+  init: &RequestInit,
+) -> Result<Response> {
+  fetch_with_str_and_init(resource, init)
+    .await?
+    .dyn_into()
+    .map_err(|err| anyhow!("error converting fetch to Response {:#?}", err))
+}
+
 pub async fn fetch_with_str(resource: &str) -> Result<JsValue> {
   JsFuture::from(window()?.fetch_with_str(resource))
     .await
     .map_err(|err| anyhow!("error fetching {:#?}", err))
 }
 
+pub async fn fetch_with_str_and_init(
+  resource: &str,
+  init: &RequestInit,
+) -> Result<JsValue> {
+  JsFuture::from(window()?.fetch_with_str_and_init(resource, init))
+    .await
+    .map_err(|err| anyhow!("error fetching {:#?}", err))
+}
+
+pub fn encode_uri_component(component: &str) -> String {
+  js_sys::encode_uri_component(component).into()
+}
+
 pub fn find_html_element_by_id(id: &str) -> Result<HtmlElement> {
   document()
     .and_then(|doc| {
@@ -137,6 +216,18 @@ pub fn find_ui() -> Result<Element> {
   })
 }
 
+pub fn gamepads() -> Result<Vec<Gamepad>> {
+  Ok(
+    window()?
+      .navigator()
+      .get_gamepads()
+      .map_err(|err| anyhow!("Could not get gamepads {:#?}", err))?
+      .iter()
+      .filter_map(|gamepad| gamepad.dyn_into::<Gamepad>().ok())
+      .collect(),
+  )
+}
+
 pub fn hide_ui() -> Result<()> {
   let ui = find_ui()?;
   if let Some(child) = ui.first_child() {
@@ -153,6 +244,20 @@ pub fn hide_ui() -> Result<()> {
   }
 }
 
+pub fn input_value(id: &str) -> Result<String> {
+  find_html_element_by_id(id)?
+    .dyn_into::<HtmlInputElement>()
+    .map(|input| input.value())
+    .map_err(|element| anyhow!("Error converting {:#?} to input", element))
+}
+
+pub async fn load_font(font: &str) -> Result<()> {
+  JsFuture::from(document()?.fonts().load(font))
+    .await
+    .map(|_faces| ())
+    .map_err(|err| anyhow!("Could not load font {} {:#?}", font, err))
+}
+
 pub fn new_image() -> Result<HtmlImageElement> {
   HtmlImageElement::new()
     .map_err(|err| anyhow!("Could not create HtmlImageElement: {:#?}", err))
@@ -167,12 +272,51 @@ pub fn now() -> Result<f64> {
   )
 }
 
+pub fn parse_json(json: &str) -> Result<JsValue> {
+  js_sys::JSON::parse(json)
+    .map_err(|err| anyhow!("Could not parse JSON {:#?}", err))
+}
+
+pub fn query_parameter(name: &str) -> Result<Option<String>> {
+  let search = window()?
+    .location()
+    .search()
+    .map_err(|err| anyhow!("Could not get location search {:#?}", err))?;
+  let params = UrlSearchParams::new_with_str(&search)
+    .map_err(|err| anyhow!("Could not parse query string {:#?}", err))?;
+  Ok(params.get(name))
+}
+
+async fn response_json(resp: Response) -> Result<JsValue> {
+  JsFuture::from(
+    resp
+      .json()
+      .map_err(|err| anyhow!("Could not get JSON from response {:#?}", err))?,
+  )
+  .await
+  .map_err(|err| anyhow!("error fetching JSON {:#?}", err))
+}
+
 pub fn request_animation_frame(callback: &LoopClosure) -> Result<i32> {
   window()?
     .request_animation_frame(callback.as_ref().unchecked_ref())
     .map_err(|err| anyhow!("Cannot request animation frame {:#?}", err))
 }
 
+pub fn set_inner_html(
+  id: &str,
+  html: &str,
+) -> Result<()> {
+  find_html_element_by_id(id).map(|element| element.set_inner_html(html))
+}
+
+pub fn set_inner_text(
+  id: &str,
+  text: &str,
+) -> Result<()> {
+  find_html_element_by_id(id).map(|element| element.set_inner_text(text))
+}
+
 pub fn spawn_local<F>(future: F)
 where
   F: Future<Output = ()> + 'static,
@@ -180,6 +324,12 @@ where
   wasm_bindgen_futures::spawn_local(future);
 }
 
+pub fn stringify_json(value: &JsValue) -> Result<String> {
+  js_sys::JSON::stringify(value)
+    .map(String::from)
+    .map_err(|err| anyhow!("Could not stringify JSON {:#?}", err))
+}
+
 pub fn window() -> Result<Window> {
   web_sys::window().ok_or_else(|| anyhow!("No Window Found"))
 }
@@ -0,0 +1,53 @@
+use super::{parse_json, stringify_json, window};
+use anyhow::{anyhow, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use web_sys::Storage;
+
+pub fn load<T: DeserializeOwned>(key: &str) -> Result<Option<T>> {
+  match load_item(key)? {
+    Some(json) => serde_wasm_bindgen::from_value(parse_json(&json)?)
+      .map(Some)
+      .map_err(|err| {
+        anyhow!("Could not parse {} from storage {:#?}", key, err)
+      }),
+    None => Ok(None),
+  }
+}
+
+fn load_item(key: &str) -> Result<Option<String>> {
+  local_storage()?
+    .get_item(key)
+    .map_err(|err| anyhow!("Could not load {} from storage {:#?}", key, err))
+}
+
+fn local_storage() -> Result<Storage> {
+  window()?
+    .local_storage()
+    .map_err(|err| anyhow!("Could not get local storage {:#?}", err))?
+    .ok_or_else(|| anyhow!("No Local Storage Found"))
+}
+
+pub fn remove(key: &str) -> Result<()> {
+  local_storage()?
+    .remove_item(key)
+    .map_err(|err| anyhow!("Could not remove {} from storage {:#?}", key, err))
+}
+
+pub fn save<T: Serialize>(
+  key: &str,
+  value: &T,
+) -> Result<()> {
+  let value = value
+    .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+    .map_err(|err| anyhow!("Could not serialize {} {:#?}", key, err))?;
+  save_item(key, &stringify_json(&value)?)
+}
+
+fn save_item(
+  key: &str,
+  value: &str,
+) -> Result<()> {
+  local_storage()?
+    .set_item(key, value)
+    .map_err(|err| anyhow!("Could not save {} to storage {:#?}", key, err))
+}
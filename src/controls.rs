@@ -0,0 +1,162 @@
+use crate::engine::KeyState;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Action {
+  Jump,
+  Pause,
+  Run,
+  Slide,
+}
+
+impl Action {
+  pub const ALL: [Action; 4] = [
+    Action::Run,
+    Action::Jump,
+    Action::Slide,
+    Action::Pause,
+  ];
+
+  pub fn id(&self) -> &str {
+    match self {
+      Action::Jump => "jump",
+      Action::Pause => "pause",
+      Action::Run => "run",
+      Action::Slide => "slide",
+    }
+  }
+
+  pub fn label(&self) -> &str {
+    match self {
+      Action::Jump => "Jump",
+      Action::Pause => "Pause",
+      Action::Run => "Run",
+      Action::Slide => "Slide",
+    }
+  }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Bindings {
+  codes: HashMap<Action, Vec<String>>,
+}
+
+impl Default for Bindings {
+  fn default() -> Self {
+    let mut codes = HashMap::new();
+    codes.insert(
+      Action::Jump,
+      vec![
+        "Space".into(),
+        "ArrowUp".into(),
+        "GamepadButton0".into(),
+        "Tap".into(),
+        "VirtualJump".into(),
+      ],
+    );
+    codes.insert(
+      Action::Pause,
+      vec![
+        "Escape".into(),
+        "KeyP".into(),
+        "GamepadButton9".into(),
+      ],
+    );
+    codes.insert(
+      Action::Run,
+      vec![
+        "ArrowRight".into(),
+        "KeyD".into(),
+        "GamepadButton15".into(),
+        "GamepadAxis0Positive".into(),
+        "SwipeRight".into(),
+      ],
+    );
+    codes.insert(
+      Action::Slide,
+      vec![
+        "ArrowDown".into(),
+        "KeyS".into(),
+        "GamepadButton1".into(),
+        "GamepadButton13".into(),
+        "GamepadAxis1Positive".into(),
+        "SwipeDown".into(),
+        "VirtualSlide".into(),
+      ],
+    );
+    Bindings {
+      codes,
+    }
+  }
+}
+
+impl Bindings {
+  pub fn bind(
+    &mut self,
+    action: Action,
+    code: &str,
+  ) -> Result<()> {
+    if let Some((bound_action, _codes)) =
+      self.codes.iter().find(|(bound_action, codes)| {
+        **bound_action != action && codes.len() == 1 && codes[0] == code
+      })
+    {
+      return Err(anyhow!(
+        "{} is the only binding for {}",
+        code,
+        bound_action.label()
+      ));
+    }
+    self.codes.values_mut().for_each(|codes| {
+      codes.retain(|bound_code| bound_code != code);
+    });
+    self.codes.entry(action).or_default().push(code.into());
+    Ok(())
+  }
+
+  pub fn codes(
+    &self,
+    action: Action,
+  ) -> &[String] {
+    self
+      .codes
+      .get(&action)
+      .map(Vec::as_slice)
+      .unwrap_or_default()
+  }
+
+  pub fn is_pressed(
+    &self,
+    keystate: &KeyState,
+    action: Action,
+  ) -> bool {
+    self
+      .codes(action)
+      .iter()
+      .any(|code| keystate.is_pressed(code) || keystate.just_pressed(code))
+  }
+
+  pub fn just_pressed(
+    &self,
+    keystate: &KeyState,
+    action: Action,
+  ) -> bool {
+    self
+      .codes(action)
+      .iter()
+      .any(|code| keystate.just_pressed(code))
+  }
+
+  pub fn tapped(
+    &self,
+    keystate: &KeyState,
+    action: Action,
+  ) -> bool {
+    self
+      .codes(action)
+      .iter()
+      .any(|code| keystate.just_pressed(code) && keystate.just_released(code))
+  }
+}
@@ -0,0 +1,591 @@
+use self::dog_states::*;
+use crate::engine::{Cell, ImageElement, Point, Rect, Renderer, Sheet};
+use crate::game::Obstacle;
+
+const HITBOX_HEIGHT_OFFSET: i16 = 24;
+const HITBOX_WIDTH_OFFSET: i16 = 24;
+const HITBOX_X_OFFSET: i16 = 12;
+const HITBOX_Y_OFFSET: i16 = 20;
+const LEASH_LENGTH: i16 = 64;
+const LOOKAHEAD: i16 = 48;
+
+#[derive(Clone)]
+enum DogStateMachine {
+  Hurt(DogState<Hurt>),
+  Idle(DogState<Idle>),
+  Jumping(DogState<Jumping>),
+  Running(DogState<Running>),
+}
+
+pub enum DogEvent {
+  Jump,
+  KnockOut,
+  Run,
+  Stop,
+  Update(i16),
+}
+
+impl DogStateMachine {
+  fn context(&self) -> &DogContext {
+    match self {
+      DogStateMachine::Hurt(state) => state.context(),
+      DogStateMachine::Idle(state) => state.context(),
+      DogStateMachine::Jumping(state) => state.context(),
+      DogStateMachine::Running(state) => state.context(),
+    }
+  }
+
+  fn frame_name(&self) -> &str {
+    match self {
+      DogStateMachine::Hurt(state) => state.frame_name(),
+      DogStateMachine::Idle(state) => state.frame_name(),
+      DogStateMachine::Jumping(state) => state.frame_name(),
+      DogStateMachine::Running(state) => state.frame_name(),
+    }
+  }
+
+  fn transition(
+    self,
+    event: DogEvent,
+  ) -> Self {
+    match (self.clone(), event) {
+      (DogStateMachine::Hurt(state), DogEvent::Update(leash_x)) => {
+        state.update(leash_x).into()
+      },
+      (DogStateMachine::Idle(state), DogEvent::Run) => state.run().into(),
+      (DogStateMachine::Idle(state), DogEvent::Update(leash_x)) => {
+        state.update(leash_x).into()
+      },
+      (DogStateMachine::Jumping(state), DogEvent::KnockOut) => {
+        state.knock_out().into()
+      },
+      (DogStateMachine::Jumping(state), DogEvent::Update(leash_x)) => {
+        state.update(leash_x).into()
+      },
+      (DogStateMachine::Running(state), DogEvent::Jump) => state.jump().into(),
+      (DogStateMachine::Running(state), DogEvent::KnockOut) => {
+        state.knock_out().into()
+      },
+      (DogStateMachine::Running(state), DogEvent::Stop) => state.stop().into(),
+      (DogStateMachine::Running(state), DogEvent::Update(leash_x)) => {
+        state.update(leash_x).into()
+      },
+      _ => self,
+    }
+  }
+}
+
+impl From<DogState<Hurt>> for DogStateMachine {
+  fn from(state: DogState<Hurt>) -> Self {
+    DogStateMachine::Hurt(state)
+  }
+}
+
+impl From<DogState<Idle>> for DogStateMachine {
+  fn from(state: DogState<Idle>) -> Self {
+    DogStateMachine::Idle(state)
+  }
+}
+
+impl From<DogState<Jumping>> for DogStateMachine {
+  fn from(state: DogState<Jumping>) -> Self {
+    DogStateMachine::Jumping(state)
+  }
+}
+
+impl From<DogState<Running>> for DogStateMachine {
+  fn from(state: DogState<Running>) -> Self {
+    DogStateMachine::Running(state)
+  }
+}
+
+impl From<HurtEndState> for DogStateMachine {
+  fn from(end_state: HurtEndState) -> Self {
+    match end_state {
+      HurtEndState::Complete(running_state) => running_state.into(),
+      HurtEndState::Hurt(hurt_state) => hurt_state.into(),
+    }
+  }
+}
+
+impl From<JumpingEndState> for DogStateMachine {
+  fn from(end_state: JumpingEndState) -> Self {
+    match end_state {
+      JumpingEndState::Jumping(jumping_state) => jumping_state.into(),
+      JumpingEndState::Landing(running_state) => running_state.into(),
+    }
+  }
+}
+
+pub struct Dog {
+  image: ImageElement,
+  previous_position: Point,
+  sprite_sheet: Sheet,
+  state_machine: DogStateMachine,
+}
+
+impl Dog {
+  pub fn new(
+    image: ImageElement,
+    sheet: Sheet,
+    x: i16,
+  ) -> Self {
+    let state_machine = DogStateMachine::Idle(DogState::new(x));
+    Dog {
+      image,
+      previous_position: state_machine.context().position,
+      sprite_sheet: sheet,
+      state_machine,
+    }
+  }
+
+  fn bounding_box(&self) -> Rect {
+    let mut bounding_box = self.destination_box();
+    bounding_box.position.x += HITBOX_X_OFFSET;
+    bounding_box.width -= HITBOX_WIDTH_OFFSET;
+    bounding_box.position.y += HITBOX_Y_OFFSET;
+    bounding_box.height -= HITBOX_HEIGHT_OFFSET;
+    bounding_box
+  }
+
+  fn current_sprite(&self) -> Option<&Cell> {
+    self.sprite_sheet.frames.get(&self.frame_name())
+  }
+
+  fn destination_box(&self) -> Rect {
+    self.destination_box_at(&self.state_machine.context().position)
+  }
+
+  fn destination_box_at(
+    &self,
+    position: &Point,
+  ) -> Rect {
+    let sprite = self.current_sprite().expect("Cell not found");
+    Rect {
+      position: Point {
+        x: position.x + sprite.sprite_source_size.x,
+        y: position.y + sprite.sprite_source_size.y,
+      },
+      width: sprite.frame.w,
+      height: sprite.frame.h,
+    }
+  }
+
+  pub fn draw(
+    &self,
+    renderer: &dyn Renderer,
+    alpha: f32,
+  ) {
+    let position = self
+      .previous_position
+      .interpolate(&self.state_machine.context().position, alpha);
+    let sprite = self.current_sprite().expect("Cell not found");
+    renderer.draw_image(
+      &self.image,
+      &Rect {
+        position: Point {
+          x: sprite.frame.x,
+          y: sprite.frame.y,
+        },
+        width: sprite.frame.w,
+        height: sprite.frame.h,
+      },
+      &self.destination_box_at(&position),
+    );
+  }
+
+  fn frame_name(&self) -> String {
+    format!(
+      "{} ({}).png",
+      self.state_machine.frame_name(),
+      (self.state_machine.context().frame / TICKS_PER_FRAME) + 1
+    )
+  }
+
+  pub fn leash_x(boy_bounding_box: &Rect) -> i16 {
+    boy_bounding_box.x() - LEASH_LENGTH
+  }
+
+  pub fn reset(
+    dog: Self,
+    x: i16,
+  ) -> Self {
+    Dog::new(dog.image, dog.sprite_sheet, x)
+  }
+
+  fn transition(
+    &mut self,
+    event: DogEvent,
+  ) {
+    self.state_machine = self.state_machine.clone().transition(event);
+  }
+
+  pub fn update(
+    &mut self,
+    leash_x: i16,
+    obstacles: &[Box<dyn Obstacle>],
+    running: bool,
+  ) {
+    self.transition(if running {
+      DogEvent::Run
+    } else {
+      DogEvent::Stop
+    });
+    let bounding_box = self.bounding_box();
+    let mut lookahead = bounding_box.clone();
+    lookahead.position.x = bounding_box.right();
+    lookahead.width = LOOKAHEAD;
+    if obstacles
+      .iter()
+      .any(|obstacle| obstacle.intersects(&bounding_box))
+    {
+      self.transition(DogEvent::KnockOut);
+    } else if obstacles
+      .iter()
+      .any(|obstacle| obstacle.intersects(&lookahead))
+    {
+      self.transition(DogEvent::Jump);
+    }
+    self.previous_position = self.state_machine.context().position;
+    self.transition(DogEvent::Update(leash_x));
+  }
+}
+
+pub mod dog_states {
+
+  use crate::engine::Point;
+  use crate::game::HEIGHT;
+
+  const DOG_HEIGHT: i16 = 60;
+  const FLOOR: i16 = HEIGHT - DOG_HEIGHT;
+  const GRAVITY: i16 = 1;
+  const HURT_FRAME_NAME: &str = "Hurt";
+  const HURT_FRAMES: u8 = 7; // 2 'Hurt' frames in the sheet, * 4 - 1
+  const IDLE_FRAME_NAME: &str = "Idle";
+  const IDLE_FRAMES: u8 = 7;
+  const JUMP_FRAME_NAME: &str = "Jump";
+  const JUMP_SPEED: i16 = -14;
+  const JUMPING_FRAMES: u8 = 7;
+  const KNOCKBACK_JUMP_SPEED: i16 = -8;
+  const KNOCKBACK_SPEED: i16 = -6;
+  const LEASH_LAG: i16 = 8;
+  const RUN_FRAME_NAME: &str = "Run";
+  const RUNNING_FRAMES: u8 = 15;
+  const TERMINAL_VELOCITY: i16 = 20;
+  pub const TICKS_PER_FRAME: u8 = 4;
+
+  #[derive(Clone, Copy)]
+  pub struct Hurt;
+
+  #[derive(Clone, Copy)]
+  pub struct Idle;
+
+  #[derive(Clone, Copy)]
+  pub struct Jumping;
+
+  #[derive(Clone, Copy)]
+  pub struct Running;
+
+  #[derive(Clone)]
+  pub struct DogState<S> {
+    context: DogContext,
+    _state: S,
+  }
+
+  #[derive(Clone)]
+  pub struct DogContext {
+    pub frame: u8,
+    pub position: Point,
+    pub velocity: Point,
+  }
+
+  impl DogContext {
+    fn follow(
+      mut self,
+      leash_x: i16,
+    ) -> Self {
+      let distance = leash_x - self.position.x;
+      let step = distance / LEASH_LAG;
+      self.position.x += if step == 0 {
+        distance.signum()
+      } else {
+        step
+      };
+      self
+    }
+
+    fn knock_back(mut self) -> Self {
+      self.velocity.x = KNOCKBACK_SPEED;
+      self.velocity.y = KNOCKBACK_JUMP_SPEED;
+      self
+    }
+
+    fn landed(&self) -> bool {
+      self.velocity.y > 0 && self.position.y >= FLOOR
+    }
+
+    fn reset_frame(mut self) -> Self {
+      self.frame = 0;
+      self
+    }
+
+    fn set_vertical_velocity(
+      mut self,
+      y: i16,
+    ) -> Self {
+      self.velocity.y = y;
+      self
+    }
+
+    fn stop(mut self) -> Self {
+      self.velocity.x = 0;
+      self
+    }
+
+    pub fn update(
+      mut self,
+      frame_count: u8,
+    ) -> Self {
+      if self.velocity.y < TERMINAL_VELOCITY {
+        self.velocity.y += GRAVITY;
+      }
+      if self.frame < frame_count {
+        self.frame += 1;
+      } else {
+        self.frame = 0;
+      }
+      self.position.x += self.velocity.x;
+      self.position.y += self.velocity.y;
+      if self.position.y > FLOOR {
+        self.position.y = FLOOR;
+      }
+      self
+    }
+  }
+
+  impl<S> DogState<S> {
+    pub fn context(&self) -> &DogContext {
+      &self.context
+    }
+  }
+
+  impl DogState<Hurt> {
+    pub fn frame_name(&self) -> &str {
+      HURT_FRAME_NAME
+    }
+
+    pub fn recover(self) -> DogState<Running> {
+      DogState {
+        context: self.context.reset_frame().stop(),
+        _state: Running,
+      }
+    }
+
+    pub fn update(
+      mut self,
+      _leash_x: i16,
+    ) -> HurtEndState {
+      self.context = self.context.update(HURT_FRAMES);
+      if self.context.landed() {
+        HurtEndState::Complete(self.recover())
+      } else {
+        HurtEndState::Hurt(self)
+      }
+    }
+  }
+
+  impl DogState<Idle> {
+    pub fn frame_name(&self) -> &str {
+      IDLE_FRAME_NAME
+    }
+
+    pub fn new(x: i16) -> Self {
+      DogState {
+        context: DogContext {
+          frame: 0,
+          position: Point {
+            x,
+            y: FLOOR,
+          },
+          velocity: Point {
+            x: 0,
+            y: 0,
+          },
+        },
+        _state: Idle,
+      }
+    }
+
+    pub fn run(self) -> DogState<Running> {
+      DogState {
+        context: self.context.reset_frame(),
+        _state: Running,
+      }
+    }
+
+    pub fn update(
+      mut self,
+      leash_x: i16,
+    ) -> Self {
+      self.context = self.context.update(IDLE_FRAMES).follow(leash_x);
+      self
+    }
+  }
+
+  impl DogState<Jumping> {
+    pub fn frame_name(&self) -> &str {
+      JUMP_FRAME_NAME
+    }
+
+    pub fn knock_out(self) -> DogState<Hurt> {
+      DogState {
+        context: self.context.reset_frame().knock_back(),
+        _state: Hurt,
+      }
+    }
+
+    pub fn land(self) -> DogState<Running> {
+      DogState {
+        context: self.context.reset_frame(),
+        _state: Running,
+      }
+    }
+
+    pub fn update(
+      mut self,
+      leash_x: i16,
+    ) -> JumpingEndState {
+      self.context = self.context.update(JUMPING_FRAMES).follow(leash_x);
+      if self.context.landed() {
+        JumpingEndState::Landing(self.land())
+      } else {
+        JumpingEndState::Jumping(self)
+      }
+    }
+  }
+
+  impl DogState<Running> {
+    pub fn frame_name(&self) -> &str {
+      RUN_FRAME_NAME
+    }
+
+    pub fn jump(self) -> DogState<Jumping> {
+      DogState {
+        context: self.context.reset_frame().set_vertical_velocity(JUMP_SPEED),
+        _state: Jumping,
+      }
+    }
+
+    pub fn knock_out(self) -> DogState<Hurt> {
+      DogState {
+        context: self.context.reset_frame().knock_back(),
+        _state: Hurt,
+      }
+    }
+
+    pub fn stop(self) -> DogState<Idle> {
+      DogState {
+        context: self.context.reset_frame(),
+        _state: Idle,
+      }
+    }
+
+    pub fn update(
+      mut self,
+      leash_x: i16,
+    ) -> Self {
+      self.context = self.context.update(RUNNING_FRAMES).follow(leash_x);
+      self
+    }
+  }
+
+  pub enum HurtEndState {
+    Complete(DogState<Running>),
+    Hurt(DogState<Hurt>),
+  }
+
+  pub enum JumpingEndState {
+    Jumping(DogState<Jumping>),
+    Landing(DogState<Running>),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::engine::Image;
+  use crate::game::tests::assets;
+  use crate::game::{Barrier, HEIGHT};
+
+  const LEASH_X: i16 = 200;
+
+  fn dog(x: i16) -> Dog {
+    let assets = assets();
+    Dog::new(assets.dog_image, assets.dog_sheet, x)
+  }
+
+  fn stone(x: i16) -> Vec<Box<dyn Obstacle>> {
+    let stone = assets().stone;
+    let y = HEIGHT - stone.height();
+    vec![
+      Box::new(Barrier::new(Image::new(
+        stone,
+        Point {
+          x,
+          y,
+        },
+      ))),
+    ]
+  }
+
+  #[test]
+  fn update_follows_the_leash() {
+    let mut dog = dog(0);
+    let mut previous_x = 0;
+    for _tick in 0..100 {
+      dog.update(LEASH_X, &[], true);
+      let x = dog.state_machine.context().position.x;
+      assert!(x > previous_x || x == LEASH_X);
+      previous_x = x;
+    }
+    assert!(matches!(dog.state_machine, DogStateMachine::Running(_)));
+    assert_eq!(previous_x, LEASH_X);
+    dog.update(0, &[], false);
+    assert!(matches!(dog.state_machine, DogStateMachine::Idle(_)));
+    assert!(dog.state_machine.context().position.x < LEASH_X);
+  }
+
+  #[test]
+  fn update_is_knocked_back_by_an_obstacle_then_recovers() {
+    let mut dog = dog(LEASH_X);
+    let obstacles = stone(dog.bounding_box().x());
+    dog.update(LEASH_X, &obstacles, true);
+    assert!(matches!(dog.state_machine, DogStateMachine::Hurt(_)));
+    assert!(dog.state_machine.context().position.x < LEASH_X);
+    dog.update(LEASH_X, &obstacles, true);
+    assert!(matches!(dog.state_machine, DogStateMachine::Hurt(_)));
+    let mut ticks = 0;
+    while matches!(dog.state_machine, DogStateMachine::Hurt(_)) {
+      dog.update(LEASH_X, &[], true);
+      ticks += 1;
+      assert!(ticks < 100, "The dog never recovered");
+    }
+    assert!(matches!(dog.state_machine, DogStateMachine::Running(_)));
+  }
+
+  #[test]
+  fn update_jumps_at_an_obstacle_ahead_then_lands() {
+    let mut dog = dog(LEASH_X);
+    let obstacles = stone(dog.bounding_box().right() + LOOKAHEAD / 2);
+    dog.update(LEASH_X, &obstacles, true);
+    assert!(matches!(dog.state_machine, DogStateMachine::Jumping(_)));
+    assert!(dog.state_machine.context().velocity.y < 0);
+    let mut ticks = 0;
+    while matches!(dog.state_machine, DogStateMachine::Jumping(_)) {
+      dog.update(LEASH_X, &[], true);
+      ticks += 1;
+      assert!(ticks < 100, "The dog never landed");
+    }
+    assert!(matches!(dog.state_machine, DogStateMachine::Running(_)));
+  }
+}
@@ -5,22 +5,33 @@ use async_trait::async_trait;
 use futures::channel::mpsc::unbounded;
 use futures::channel::{mpsc::UnboundedReceiver, oneshot::channel};
 use js_sys::ArrayBuffer;
-use serde::Deserialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::cell::Ref;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::{cell::RefCell, rc::Rc, sync::Mutex};
 use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
-use web_sys::{AudioBuffer, AudioContext, HtmlElement};
+use web_sys::{
+  AudioBuffer, AudioContext, GainNode, Gamepad, GamepadButton, GamepadEvent,
+  HtmlElement, PointerEvent,
+};
 use web_sys::{CanvasRenderingContext2d, HtmlImageElement};
 
 #[async_trait(?Send)]
 pub trait Game {
   fn draw(
     &self,
-    renderer: &Renderer,
+    renderer: &dyn Renderer,
+    alpha: f32,
   );
 
   async fn initialize(&self) -> Result<Box<dyn Game>>;
 
+  fn virtual_buttons(&self) -> Vec<VirtualButton>;
+
+  fn pause(&mut self);
+
+  fn resume(&mut self);
+
   fn update(
     &mut self,
     keystate: &KeyState,
@@ -28,10 +39,16 @@ pub trait Game {
 }
 
 const FRAME_SIZE: f32 = 1.0 / 60.0 * 1000.0;
+const GAMEPAD_AXIS_THRESHOLD: f64 = 0.5;
+const SWIPE_DISTANCE: i16 = 40;
+const TAP_DISTANCE: i16 = 20;
+const VIRTUAL_BUTTON_COLOR: &str = "rgba(255, 255, 255, 0.4)";
+const MAX_FRAME_DELTA: f32 = FRAME_SIZE * 4.0;
 
 pub struct GameLoop {
   accumulated_delta: f32,
   last_frame: f64,
+  paused: bool,
 }
 
 type SharedLoopClosure = Rc<RefCell<Option<LoopClosure>>>;
@@ -39,26 +56,42 @@ type SharedLoopClosure = Rc<RefCell<Option<LoopClosure>>>;
 impl GameLoop {
   pub async fn start(game: impl Game + 'static) -> Result<()> {
     let mut keyevent_receiver = prepare_input()?;
+    let mut gamepad_receiver = prepare_gamepads()?;
+    let mut page_focus_receiver = prepare_page_focus()?;
+    let mut pointer_receiver = prepare_pointer_input()?;
     let mut game = game.initialize().await?;
+    let mut touch_controls = TouchControls::new(game.virtual_buttons());
     let mut game_loop = GameLoop {
       accumulated_delta: 0.0,
       last_frame: browser::now()?,
+      paused: false,
     };
-    let renderer = Renderer {
-      context: browser::context()?,
-    };
+    let renderer = CanvasRenderer::new(browser::context()?);
     let f: SharedLoopClosure = Rc::new(RefCell::new(None));
     let g = f.clone();
     let mut keystate = KeyState::new();
+    let mut gamepads = Gamepads::new();
     *g.borrow_mut() = Some(browser::create_raf_closure(move |perf: f64| {
       process_input(&mut keystate, &mut keyevent_receiver);
-      game_loop.accumulated_delta += (perf - game_loop.last_frame) as f32;
-      while game_loop.accumulated_delta > FRAME_SIZE {
-        game.update(&keystate);
-        game_loop.accumulated_delta -= FRAME_SIZE;
+      process_gamepads(&mut keystate, &mut gamepads, &mut gamepad_receiver);
+      process_pointer_input(
+        &mut keystate,
+        &mut touch_controls,
+        &mut pointer_receiver,
+      );
+      process_page_focus(&mut game_loop, &mut game, &mut page_focus_receiver);
+      if !game_loop.paused {
+        let frame_delta = (perf - game_loop.last_frame) as f32;
+        game_loop.accumulated_delta += frame_delta.min(MAX_FRAME_DELTA);
+        while game_loop.accumulated_delta > FRAME_SIZE {
+          game.update(&keystate);
+          keystate.tick();
+          game_loop.accumulated_delta -= FRAME_SIZE;
+        }
       }
       game_loop.last_frame = perf;
-      game.draw(&renderer);
+      game.draw(&renderer, game_loop.accumulated_delta / FRAME_SIZE);
+      touch_controls.draw(&renderer);
       let _result: Result<i32, anyhow::Error> =
         browser::request_animation_frame(f.borrow().as_ref().unwrap());
     }));
@@ -69,6 +102,15 @@ impl GameLoop {
     )?;
     Ok(())
   }
+
+  fn pause(&mut self) {
+    self.paused = true;
+  }
+
+  fn resume(&mut self) {
+    self.paused = false;
+    self.accumulated_delta = 0.0;
+  }
 }
 
 pub fn add_click_handler(elem: HtmlElement) -> UnboundedReceiver<()> {
@@ -82,7 +124,7 @@ pub fn add_click_handler(elem: HtmlElement) -> UnboundedReceiver<()> {
   click_receiver
 }
 
-pub async fn load_image(source: &str) -> Result<HtmlImageElement> {
+pub async fn load_image(source: &str) -> Result<ImageElement> {
   let image: HtmlImageElement = browser::new_image()?;
   let (complete_tx, complete_rx) = channel::<Result<()>>();
   let success_tx = Rc::new(Mutex::new(Some(complete_tx)));
@@ -107,7 +149,7 @@ pub async fn load_image(source: &str) -> Result<HtmlImageElement> {
   image.set_onerror(Some(error_callback.as_ref().unchecked_ref()));
   image.set_src(source);
   complete_rx.await??;
-  Ok(image)
+  Ok(ImageElement::new(source, image))
 }
 
 enum KeyPress {
@@ -138,38 +180,447 @@ fn prepare_input() -> Result<UnboundedReceiver<KeyPress>> {
   Ok(keyevent_receiver)
 }
 
+enum GamepadConnection {
+  Connected(u32),
+  Disconnected(u32),
+}
+
+fn prepare_gamepads() -> Result<UnboundedReceiver<GamepadConnection>> {
+  let (connected_sender, gamepad_receiver) = unbounded();
+  let connected_sender = Rc::new(RefCell::new(connected_sender));
+  let disconnected_sender = Rc::clone(&connected_sender);
+  let ongamepadconnected =
+    browser::closure_wrap(Box::new(move |event: GamepadEvent| {
+      if let Some(gamepad) = event.gamepad() {
+        log!("Gamepad {} connected: {}", gamepad.index(), gamepad.id());
+        let _result = connected_sender
+          .borrow_mut()
+          .start_send(GamepadConnection::Connected(gamepad.index()));
+      }
+    }) as Box<dyn FnMut(GamepadEvent)>);
+  let ongamepaddisconnected =
+    browser::closure_wrap(Box::new(move |event: GamepadEvent| {
+      if let Some(gamepad) = event.gamepad() {
+        log!("Gamepad {} disconnected: {}", gamepad.index(), gamepad.id());
+        let _result = disconnected_sender
+          .borrow_mut()
+          .start_send(GamepadConnection::Disconnected(gamepad.index()));
+      }
+    }) as Box<dyn FnMut(GamepadEvent)>);
+  let window = browser::window()?;
+  browser::add_event_listener(
+    &window,
+    "gamepadconnected",
+    &ongamepadconnected,
+  )?;
+  browser::add_event_listener(
+    &window,
+    "gamepaddisconnected",
+    &ongamepaddisconnected,
+  )?;
+  ongamepadconnected.forget();
+  ongamepaddisconnected.forget();
+  Ok(gamepad_receiver)
+}
+
+struct Gamepads {
+  connected: HashSet<u32>,
+  pressed_codes: HashSet<String>,
+}
+
+impl Gamepads {
+  fn new() -> Self {
+    Gamepads {
+      connected: HashSet::new(),
+      pressed_codes: HashSet::new(),
+    }
+  }
+
+  fn poll(&self) -> HashSet<String> {
+    if self.connected.is_empty() {
+      return HashSet::new();
+    }
+    browser::gamepads()
+      .unwrap_or_default()
+      .iter()
+      .filter(|gamepad| self.connected.contains(&gamepad.index()))
+      .flat_map(gamepad_codes)
+      .collect()
+  }
+}
+
+fn gamepad_codes(gamepad: &Gamepad) -> Vec<String> {
+  let buttons = gamepad.buttons();
+  let axes = gamepad.axes();
+  let button_codes = buttons
+    .iter()
+    .enumerate()
+    .filter(|(_index, button)| {
+      button
+        .dyn_ref::<GamepadButton>()
+        .is_some_and(GamepadButton::pressed)
+    })
+    .map(|(index, _button)| format!("GamepadButton{}", index));
+  let axis_codes =
+    axes
+      .iter()
+      .enumerate()
+      .filter_map(|(index, axis)| match axis.as_f64() {
+        Some(value) if value > GAMEPAD_AXIS_THRESHOLD => {
+          Some(format!("GamepadAxis{}Positive", index))
+        },
+        Some(value) if value < -GAMEPAD_AXIS_THRESHOLD => {
+          Some(format!("GamepadAxis{}Negative", index))
+        },
+        _ => None,
+      });
+  button_codes.chain(axis_codes).collect()
+}
+
+fn process_gamepads(
+  state: &mut KeyState,
+  gamepads: &mut Gamepads,
+  gamepad_receiver: &mut UnboundedReceiver<GamepadConnection>,
+) {
+  loop {
+    match gamepad_receiver.try_next() {
+      Ok(None) => break,
+      Err(_err) => break,
+      Ok(Some(connection)) => match connection {
+        GamepadConnection::Connected(index) => {
+          gamepads.connected.insert(index);
+        },
+        GamepadConnection::Disconnected(index) => {
+          gamepads.connected.remove(&index);
+        },
+      },
+    }
+  }
+  let pressed_codes = gamepads.poll();
+  gamepads
+    .pressed_codes
+    .difference(&pressed_codes)
+    .for_each(|code| state.set_released(code));
+  pressed_codes
+    .difference(&gamepads.pressed_codes)
+    .for_each(|code| state.set_pressed(code));
+  gamepads.pressed_codes = pressed_codes;
+}
+
+enum PointerPress {
+  Cancel(i32),
+  Down(i32, Point, bool),
+  Up(i32, Point),
+}
+
+fn prepare_pointer_input() -> Result<UnboundedReceiver<PointerPress>> {
+  let (pointerdown_sender, pointer_receiver) = unbounded();
+  let pointerdown_sender = Rc::new(RefCell::new(pointerdown_sender));
+  let pointerup_sender = Rc::clone(&pointerdown_sender);
+  let pointercancel_sender = Rc::clone(&pointerdown_sender);
+  let canvas = browser::canvas()?;
+  let pointerdown_canvas = canvas.clone();
+  let pointerup_canvas = canvas.clone();
+  let onpointerdown =
+    browser::closure_wrap(Box::new(move |event: PointerEvent| {
+      event.prevent_default();
+      let _result =
+        pointerdown_sender
+          .borrow_mut()
+          .start_send(PointerPress::Down(
+            event.pointer_id(),
+            canvas_point(&pointerdown_canvas, &event),
+            event.pointer_type() == "touch",
+          ));
+    }) as Box<dyn FnMut(PointerEvent)>);
+  let onpointerup =
+    browser::closure_wrap(Box::new(move |event: PointerEvent| {
+      let _result = pointerup_sender.borrow_mut().start_send(PointerPress::Up(
+        event.pointer_id(),
+        canvas_point(&pointerup_canvas, &event),
+      ));
+    }) as Box<dyn FnMut(PointerEvent)>);
+  let onpointercancel =
+    browser::closure_wrap(Box::new(move |event: PointerEvent| {
+      let _result = pointercancel_sender
+        .borrow_mut()
+        .start_send(PointerPress::Cancel(event.pointer_id()));
+    }) as Box<dyn FnMut(PointerEvent)>);
+  browser::add_event_listener(&canvas, "pointerdown", &onpointerdown)?;
+  browser::add_event_listener(&canvas, "pointerup", &onpointerup)?;
+  browser::add_event_listener(&canvas, "pointercancel", &onpointercancel)?;
+  onpointerdown.forget();
+  onpointerup.forget();
+  onpointercancel.forget();
+  Ok(pointer_receiver)
+}
+
+fn canvas_point(
+  canvas: &web_sys::HtmlCanvasElement,
+  event: &PointerEvent,
+) -> Point {
+  let scale_x = canvas.width() as f32 / canvas.client_width().max(1) as f32;
+  let scale_y = canvas.height() as f32 / canvas.client_height().max(1) as f32;
+  Point {
+    x: (event.offset_x() as f32 * scale_x) as i16,
+    y: (event.offset_y() as f32 * scale_y) as i16,
+  }
+}
+
+pub struct VirtualButton {
+  pub code: String,
+  pub rect: Rect,
+}
+
+enum PointerStart {
+  Button(String),
+  Gesture(Point),
+}
+
+struct TouchControls {
+  buttons: Vec<VirtualButton>,
+  pointers: HashMap<i32, PointerStart>,
+  visible: bool,
+}
+
+impl TouchControls {
+  fn new(buttons: Vec<VirtualButton>) -> Self {
+    TouchControls {
+      buttons,
+      pointers: HashMap::new(),
+      visible: false,
+    }
+  }
+
+  fn button_at(
+    &self,
+    position: &Point,
+  ) -> Option<&VirtualButton> {
+    if !self.visible {
+      return None;
+    }
+    self
+      .buttons
+      .iter()
+      .find(|button| button.rect.contains(position))
+  }
+
+  fn draw(
+    &self,
+    renderer: &dyn Renderer,
+  ) {
+    if self.visible {
+      self.buttons.iter().for_each(|button| {
+        renderer.fill_rect(&button.rect, VIRTUAL_BUTTON_COLOR);
+      });
+    }
+  }
+}
+
+fn gesture_code(
+  start: &Point,
+  end: &Point,
+) -> Option<&'static str> {
+  let dx = end.x - start.x;
+  let dy = end.y - start.y;
+  if dy > SWIPE_DISTANCE && dy > dx.abs() {
+    Some("SwipeDown")
+  } else if dx > SWIPE_DISTANCE && dx > dy.abs() {
+    Some("SwipeRight")
+  } else if dx.abs() < TAP_DISTANCE && dy.abs() < TAP_DISTANCE {
+    Some("Tap")
+  } else {
+    None
+  }
+}
+
+fn process_pointer_input(
+  state: &mut KeyState,
+  touch_controls: &mut TouchControls,
+  pointer_receiver: &mut UnboundedReceiver<PointerPress>,
+) {
+  loop {
+    match pointer_receiver.try_next() {
+      Ok(None) => break,
+      Err(_err) => break,
+      Ok(Some(press)) => match press {
+        PointerPress::Cancel(id) => {
+          if let Some(PointerStart::Button(code)) =
+            touch_controls.pointers.remove(&id)
+          {
+            state.set_released(&code);
+          }
+        },
+        PointerPress::Down(id, position, touch) => {
+          touch_controls.visible |= touch;
+          let start = match touch_controls.button_at(&position) {
+            Some(button) => {
+              state.set_pressed(&button.code);
+              PointerStart::Button(button.code.clone())
+            },
+            None => PointerStart::Gesture(position),
+          };
+          touch_controls.pointers.insert(id, start);
+        },
+        PointerPress::Up(id, position) => {
+          match touch_controls.pointers.remove(&id) {
+            Some(PointerStart::Button(code)) => state.set_released(&code),
+            Some(PointerStart::Gesture(start)) => {
+              if let Some(code) = gesture_code(&start, &position) {
+                state.set_pressed(code);
+                state.set_released(code);
+              }
+            },
+            None => (),
+          }
+        },
+      },
+    }
+  }
+}
+
+enum PageFocus {
+  Gained,
+  Lost,
+}
+
+fn prepare_page_focus() -> Result<UnboundedReceiver<PageFocus>> {
+  let (visibility_sender, page_focus_receiver) = unbounded();
+  let visibility_sender = Rc::new(RefCell::new(visibility_sender));
+  let blur_sender = Rc::clone(&visibility_sender);
+  let focus_sender = Rc::clone(&visibility_sender);
+  let onvisibilitychange = browser::closure_wrap(Box::new(move || {
+    let page_focus = match browser::document() {
+      Ok(document) if document.hidden() => PageFocus::Lost,
+      _ => PageFocus::Gained,
+    };
+    let _result = visibility_sender.borrow_mut().start_send(page_focus);
+  }) as Box<dyn FnMut()>);
+  let onblur = browser::closure_wrap(Box::new(move || {
+    let _result = blur_sender.borrow_mut().start_send(PageFocus::Lost);
+  }) as Box<dyn FnMut()>);
+  let onfocus = browser::closure_wrap(Box::new(move || {
+    let _result = focus_sender.borrow_mut().start_send(PageFocus::Gained);
+  }) as Box<dyn FnMut()>);
+  browser::document()?
+    .set_onvisibilitychange(Some(onvisibilitychange.as_ref().unchecked_ref()));
+  browser::window()?.set_onblur(Some(onblur.as_ref().unchecked_ref()));
+  browser::window()?.set_onfocus(Some(onfocus.as_ref().unchecked_ref()));
+  onvisibilitychange.forget();
+  onblur.forget();
+  onfocus.forget();
+  Ok(page_focus_receiver)
+}
+
+fn process_page_focus(
+  game_loop: &mut GameLoop,
+  game: &mut Box<dyn Game>,
+  page_focus_receiver: &mut UnboundedReceiver<PageFocus>,
+) {
+  loop {
+    match page_focus_receiver.try_next() {
+      Ok(None) => break,
+      Err(_err) => break,
+      Ok(Some(page_focus)) => match page_focus {
+        PageFocus::Gained => {
+          game_loop.resume();
+          game.resume();
+        },
+        PageFocus::Lost => {
+          game_loop.pause();
+          game.pause();
+        },
+      },
+    }
+  }
+}
+
+#[derive(Clone, Default)]
 pub struct KeyState {
-  pressed_keys: HashMap<String, web_sys::KeyboardEvent>,
+  just_pressed_keys: HashSet<String>,
+  just_released_keys: HashSet<String>,
+  pressed_keys: HashMap<String, u32>,
 }
 
 impl KeyState {
   fn new() -> Self {
     KeyState {
+      just_pressed_keys: HashSet::new(),
+      just_released_keys: HashSet::new(),
       pressed_keys: HashMap::new(),
     }
   }
 
+  fn apply(
+    &mut self,
+    entry: &InputLogEntry,
+  ) {
+    if entry.pressed {
+      self.set_pressed(&entry.code);
+    } else {
+      self.set_released(&entry.code);
+    }
+  }
+
+  pub fn held_ticks(
+    &self,
+    code: &str,
+  ) -> Option<u32> {
+    self.pressed_keys.get(code).copied()
+  }
+
   pub fn is_pressed(
     &self,
     code: &str,
   ) -> bool {
-    self.pressed_keys.contains_key(code)
+    self.held_ticks(code).is_some()
+  }
+
+  pub fn just_pressed(
+    &self,
+    code: &str,
+  ) -> bool {
+    self.just_pressed_keys.contains(code)
+  }
+
+  pub fn just_pressed_codes(&self) -> impl Iterator<Item = &String> {
+    self.just_pressed_keys.iter()
+  }
+
+  pub fn just_released(
+    &self,
+    code: &str,
+  ) -> bool {
+    self.just_released_keys.contains(code)
   }
 
   fn set_pressed(
     &mut self,
     code: &str,
-    event: web_sys::KeyboardEvent,
   ) {
     log!("set_pressed {}", code);
-    self.pressed_keys.insert(code.into(), event);
+    if !self.pressed_keys.contains_key(code) {
+      self.pressed_keys.insert(code.into(), 0);
+      self.just_pressed_keys.insert(code.into());
+    }
   }
 
   fn set_released(
     &mut self,
     code: &str,
   ) {
-    self.pressed_keys.remove(code);
+    if self.pressed_keys.remove(code).is_some() {
+      self.just_released_keys.insert(code.into());
+    }
+  }
+
+  fn tick(&mut self) {
+    self.just_pressed_keys.clear();
+    self.just_released_keys.clear();
+    self
+      .pressed_keys
+      .values_mut()
+      .for_each(|held_ticks| *held_ticks = held_ticks.saturating_add(1));
   }
 }
 
@@ -184,19 +635,179 @@ fn process_input(
       Err(_err) => break,
       Ok(Some(evt)) => match evt {
         KeyPress::KeyUp(evt) => state.set_released(&evt.code()),
-        KeyPress::KeyDown(evt) => state.set_pressed(&evt.code(), evt),
+        KeyPress::KeyDown(evt) if evt.repeat() => (),
+        KeyPress::KeyDown(evt) => state.set_pressed(&evt.code()),
       },
     }
   }
 }
 
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct InputLog {
+  #[serde(rename = "e")]
+  entries: Vec<InputLogEntry>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct InputLogEntry {
+  #[serde(rename = "t")]
+  tick: u32,
+  #[serde(rename = "c")]
+  code: String,
+  #[serde(rename = "p")]
+  pressed: bool,
+}
+
+pub struct InputRecorder {
+  log: InputLog,
+  previous: KeyState,
+  replayed: KeyState,
+  tick: u32,
+}
+
+impl Default for InputRecorder {
+  fn default() -> Self {
+    InputRecorder::new()
+  }
+}
+
+impl InputRecorder {
+  pub fn new() -> Self {
+    InputRecorder {
+      log: InputLog::default(),
+      previous: KeyState::new(),
+      replayed: KeyState::new(),
+      tick: 0,
+    }
+  }
+
+  pub fn log(&self) -> &InputLog {
+    &self.log
+  }
+
+  // Returns what a replay of the log sees, so that changes made between
+  // recorded ticks, such as while paused, play out the same way live
+  pub fn record(
+    &mut self,
+    keystate: &KeyState,
+  ) -> &KeyState {
+    let recorded = self.log.entries.len();
+    let mut codes: Vec<String> = keystate
+      .pressed_keys
+      .keys()
+      .chain(self.previous.pressed_keys.keys())
+      .chain(keystate.just_pressed_keys.iter())
+      .chain(keystate.just_released_keys.iter())
+      .cloned()
+      .collect();
+    codes.sort();
+    codes.dedup();
+    codes.iter().for_each(|code| {
+      let was_pressed = self.previous.is_pressed(code);
+      let just_pressed = keystate.just_pressed(code);
+      if keystate.is_pressed(code) {
+        if was_pressed && keystate.just_released(code) {
+          self.push_entry(code, false);
+        }
+        if !was_pressed || just_pressed {
+          self.push_entry(code, true);
+        }
+      } else {
+        if !was_pressed && just_pressed {
+          self.push_entry(code, true);
+        }
+        if was_pressed || keystate.just_released(code) {
+          self.push_entry(code, false);
+        }
+      }
+    });
+    self.replayed.tick();
+    self.log.entries[recorded..]
+      .iter()
+      .for_each(|entry| self.replayed.apply(entry));
+    self.previous = keystate.clone();
+    self.tick += 1;
+    &self.replayed
+  }
+
+  fn push_entry(
+    &mut self,
+    code: &str,
+    pressed: bool,
+  ) {
+    self.log.entries.push(InputLogEntry {
+      tick: self.tick,
+      code: code.into(),
+      pressed,
+    });
+  }
+}
+
+pub struct InputReplay {
+  keystate: KeyState,
+  log: InputLog,
+  next_entry: usize,
+  tick: u32,
+}
+
+impl InputReplay {
+  pub fn new(log: InputLog) -> Self {
+    InputReplay {
+      keystate: KeyState::new(),
+      log,
+      next_entry: 0,
+      tick: 0,
+    }
+  }
+
+  pub fn next_tick(&mut self) -> &KeyState {
+    self.keystate.tick();
+    while let Some(entry) = self.log.entries.get(self.next_entry) {
+      if entry.tick > self.tick {
+        break;
+      }
+      self.keystate.apply(entry);
+      self.next_entry += 1;
+    }
+    self.tick += 1;
+    &self.keystate
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+  pub depth: i16,
+  pub side: Side,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Point {
   pub x: i16,
   pub y: i16,
 }
 
-#[derive(Default)]
+impl Point {
+  pub fn interpolate(
+    &self,
+    next: &Point,
+    alpha: f32,
+  ) -> Point {
+    Point {
+      x: interpolate(self.x, next.x, alpha),
+      y: interpolate(self.y, next.y, alpha),
+    }
+  }
+}
+
+pub fn interpolate(
+  previous: i16,
+  current: i16,
+  alpha: f32,
+) -> i16 {
+  previous + ((current - previous) as f32 * alpha).round() as i16
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Rect {
   pub position: Point,
   pub width: i16,
@@ -220,6 +831,61 @@ impl Rect {
     self.position.y + self.height
   }
 
+  // The side is that of the other rect, which was crossed most recently
+  pub fn contact(
+    &self,
+    rect: &Rect,
+    velocity: &Point,
+  ) -> Option<Contact> {
+    if !self.intersects(rect) {
+      return None;
+    }
+    let (horizontal_side, horizontal_depth) = match velocity.x {
+      x if x > 0 => (Side::Left, self.right() - rect.x()),
+      x if x < 0 => (Side::Right, rect.right() - self.x()),
+      _ => min_depth(
+        (Side::Left, self.right() - rect.x()),
+        (Side::Right, rect.right() - self.x()),
+      ),
+    };
+    let (vertical_side, vertical_depth) = match velocity.y {
+      y if y > 0 => (Side::Top, self.bottom() - rect.y()),
+      y if y < 0 => (Side::Bottom, rect.bottom() - self.y()),
+      _ => min_depth(
+        (Side::Top, self.bottom() - rect.y()),
+        (Side::Bottom, rect.bottom() - self.y()),
+      ),
+    };
+    let horizontal_time =
+      i32::from(horizontal_depth) * i32::from(velocity.y.unsigned_abs());
+    let vertical_time =
+      i32::from(vertical_depth) * i32::from(velocity.x.unsigned_abs());
+    let horizontal = if velocity.x == 0 && velocity.y == 0 {
+      horizontal_depth < vertical_depth
+    } else {
+      velocity.x != 0 && horizontal_time < vertical_time
+    };
+    let (side, depth) = if horizontal {
+      (horizontal_side, horizontal_depth)
+    } else {
+      (vertical_side, vertical_depth)
+    };
+    Some(Contact {
+      depth,
+      side,
+    })
+  }
+
+  pub fn contains(
+    &self,
+    point: &Point,
+  ) -> bool {
+    point.x >= self.x()
+      && point.x < self.right()
+      && point.y >= self.y()
+      && point.y < self.bottom()
+  }
+
   pub fn intersects(
     &self,
     rect: &Rect,
@@ -257,6 +923,20 @@ impl Rect {
     self.position.x = x
   }
 
+  pub fn union(
+    &self,
+    rect: &Rect,
+  ) -> Rect {
+    let x = self.x().min(rect.x());
+    let y = self.y().min(rect.y());
+    Rect::new_from_x_y(
+      x,
+      y,
+      self.right().max(rect.right()) - x,
+      self.bottom().max(rect.bottom()) - y,
+    )
+  }
+
   pub fn x(&self) -> i16 {
     self.position.x
   }
@@ -266,12 +946,80 @@ impl Rect {
   }
 }
 
-pub struct Renderer {
+fn min_depth(
+  first: (Side, i16),
+  second: (Side, i16),
+) -> (Side, i16) {
+  if first.1 <= second.1 {
+    first
+  } else {
+    second
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+  Bottom,
+  Left,
+  Right,
+  Top,
+}
+
+pub trait Renderer {
+  fn clear(
+    &self,
+    rect: &Rect,
+  );
+
+  fn draw_entire_image(
+    &self,
+    image: &ImageElement,
+    position: &Point,
+  );
+
+  fn draw_image(
+    &self,
+    image: &ImageElement,
+    frame: &Rect,
+    destination: &Rect,
+  );
+
+  fn fill_circle(
+    &self,
+    center: &Point,
+    radius: i16,
+    color: &str,
+  );
+
+  fn fill_rect(
+    &self,
+    rect: &Rect,
+    color: &str,
+  );
+
+  fn fill_text(
+    &self,
+    text: &str,
+    position: &Point,
+    font: &str,
+    color: &str,
+  );
+}
+
+pub struct CanvasRenderer {
   context: CanvasRenderingContext2d,
 }
 
-impl Renderer {
-  pub fn clear(
+impl CanvasRenderer {
+  pub fn new(context: CanvasRenderingContext2d) -> Self {
+    CanvasRenderer {
+      context,
+    }
+  }
+}
+
+impl Renderer for CanvasRenderer {
+  fn clear(
     &self,
     rect: &Rect,
   ) {
@@ -283,27 +1031,33 @@ impl Renderer {
     );
   }
 
-  pub fn draw_entire_image(
+  fn draw_entire_image(
     &self,
-    image: &HtmlImageElement,
+    image: &ImageElement,
     position: &Point,
   ) {
+    let Some(element) = &image.element else {
+      return;
+    };
     let x: f64 = position.x.into();
     let y: f64 = position.y.into();
     self
       .context
-      .draw_image_with_html_image_element(image, x, y)
+      .draw_image_with_html_image_element(element, x, y)
       .expect("Drawing is throwing exceptions! Unrecoverable error.");
   }
 
-  pub fn draw_image(
+  fn draw_image(
     &self,
-    image: &HtmlImageElement,
+    image: &ImageElement,
     frame: &Rect,
     destination: &Rect,
   ) {
+    let Some(element) = &image.element else {
+      return;
+    };
     self.context.draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
-      image,
+      element,
       frame.position.x.into(),
       frame.position.y.into(),
       frame.width.into(),
@@ -314,6 +1068,221 @@ impl Renderer {
       destination.height.into(),
     ).expect("Drawin is throwing exceptions! Unrecoverable error.");
   }
+
+  fn fill_circle(
+    &self,
+    center: &Point,
+    radius: i16,
+    color: &str,
+  ) {
+    self.context.set_fill_style(&JsValue::from_str(color));
+    self.context.begin_path();
+    self
+      .context
+      .arc(
+        center.x.into(),
+        center.y.into(),
+        radius.into(),
+        0.0,
+        std::f64::consts::TAU,
+      )
+      .expect("Drawing arcs is throwing exceptions! Unrecoverable error.");
+    self.context.fill();
+  }
+
+  fn fill_rect(
+    &self,
+    rect: &Rect,
+    color: &str,
+  ) {
+    self.context.set_fill_style(&JsValue::from_str(color));
+    self.context.fill_rect(
+      rect.position.x.into(),
+      rect.position.y.into(),
+      rect.width.into(),
+      rect.height.into(),
+    );
+  }
+
+  fn fill_text(
+    &self,
+    text: &str,
+    position: &Point,
+    font: &str,
+    color: &str,
+  ) {
+    self.context.set_fill_style(&JsValue::from_str(color));
+    self.context.set_font(font);
+    self.context.set_text_baseline("top");
+    self
+      .context
+      .fill_text(text, position.x.into(), position.y.into())
+      .expect("Drawing text is throwing exceptions! Unrecoverable error.");
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DrawCall {
+  Clear(Rect),
+  FillCircle {
+    center: Point,
+    color: String,
+    radius: i16,
+  },
+  FillRect(Rect, String),
+  FillText {
+    color: String,
+    font: String,
+    position: Point,
+    text: String,
+  },
+  Image {
+    destination: Rect,
+    image_id: String,
+    source: Rect,
+  },
+}
+
+#[derive(Default)]
+pub struct RecordingRenderer {
+  draw_calls: RefCell<Vec<DrawCall>>,
+}
+
+impl RecordingRenderer {
+  pub fn new() -> Self {
+    RecordingRenderer::default()
+  }
+
+  pub fn draw_calls(&self) -> Ref<'_, Vec<DrawCall>> {
+    self.draw_calls.borrow()
+  }
+
+  pub fn take_draw_calls(&self) -> Vec<DrawCall> {
+    self.draw_calls.take()
+  }
+}
+
+impl Renderer for RecordingRenderer {
+  fn clear(
+    &self,
+    rect: &Rect,
+  ) {
+    self
+      .draw_calls
+      .borrow_mut()
+      .push(DrawCall::Clear(rect.clone()));
+  }
+
+  fn draw_entire_image(
+    &self,
+    image: &ImageElement,
+    position: &Point,
+  ) {
+    self.draw_image(
+      image,
+      &Rect::new_from_x_y(0, 0, image.width, image.height),
+      &Rect::new(*position, image.width, image.height),
+    );
+  }
+
+  fn draw_image(
+    &self,
+    image: &ImageElement,
+    frame: &Rect,
+    destination: &Rect,
+  ) {
+    self.draw_calls.borrow_mut().push(DrawCall::Image {
+      destination: destination.clone(),
+      image_id: image.id.to_string(),
+      source: frame.clone(),
+    });
+  }
+
+  fn fill_circle(
+    &self,
+    center: &Point,
+    radius: i16,
+    color: &str,
+  ) {
+    self.draw_calls.borrow_mut().push(DrawCall::FillCircle {
+      center: *center,
+      color: color.into(),
+      radius,
+    });
+  }
+
+  fn fill_rect(
+    &self,
+    rect: &Rect,
+    color: &str,
+  ) {
+    self
+      .draw_calls
+      .borrow_mut()
+      .push(DrawCall::FillRect(rect.clone(), color.into()));
+  }
+
+  fn fill_text(
+    &self,
+    text: &str,
+    position: &Point,
+    font: &str,
+    color: &str,
+  ) {
+    self.draw_calls.borrow_mut().push(DrawCall::FillText {
+      color: color.into(),
+      font: font.into(),
+      position: *position,
+      text: text.into(),
+    });
+  }
+}
+
+#[derive(Clone)]
+pub struct ImageElement {
+  element: Option<HtmlImageElement>,
+  height: i16,
+  id: Rc<str>,
+  width: i16,
+}
+
+impl ImageElement {
+  pub fn new(
+    id: &str,
+    element: HtmlImageElement,
+  ) -> Self {
+    ImageElement {
+      height: element.height() as i16,
+      id: id.into(),
+      width: element.width() as i16,
+      element: Some(element),
+    }
+  }
+
+  pub fn headless(
+    id: &str,
+    width: i16,
+    height: i16,
+  ) -> Self {
+    ImageElement {
+      element: None,
+      height,
+      id: id.into(),
+      width,
+    }
+  }
+
+  pub fn height(&self) -> i16 {
+    self.height
+  }
+
+  pub fn id(&self) -> &str {
+    &self.id
+  }
+
+  pub fn width(&self) -> i16 {
+    self.width
+  }
 }
 
 #[derive(Clone, Deserialize)]
@@ -323,12 +1292,29 @@ pub struct Cell {
   pub sprite_source_size: SheetRect,
 }
 
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct Hitboxes {
+  pub frames: BTreeMap<String, Vec<SheetRect>>,
+}
+
 #[derive(Clone, Deserialize)]
 pub struct Sheet {
   pub frames: HashMap<String, Cell>,
+  #[serde(default)]
+  pub hitboxes: HashMap<String, Vec<SheetRect>>,
 }
 
-#[derive(Clone, Deserialize)]
+impl Sheet {
+  pub fn with_hitboxes(
+    mut self,
+    hitboxes: Hitboxes,
+  ) -> Self {
+    self.hitboxes = hitboxes.frames.into_iter().collect();
+    self
+  }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct SheetRect {
   pub x: i16,
   pub y: i16,
@@ -338,19 +1324,20 @@ pub struct SheetRect {
 
 pub struct Image {
   bounding_box: Rect,
-  element: HtmlImageElement,
+  element: ImageElement,
+  previous_position: Point,
 }
 
 impl Image {
   pub fn new(
-    element: HtmlImageElement,
+    element: ImageElement,
     position: Point,
   ) -> Self {
-    let bounding_box =
-      Rect::new(position, element.width() as i16, element.height() as i16);
+    let bounding_box = Rect::new(position, element.width(), element.height());
     Self {
       bounding_box,
       element,
+      previous_position: position,
     }
   }
 
@@ -360,16 +1347,21 @@ impl Image {
 
   pub fn draw(
     &self,
-    renderer: &Renderer,
+    renderer: &dyn Renderer,
+    alpha: f32,
   ) {
-    renderer.draw_entire_image(&self.element, &self.bounding_box.position)
+    let position = self
+      .previous_position
+      .interpolate(&self.bounding_box.position, alpha);
+    renderer.draw_entire_image(&self.element, &position)
   }
 
   pub fn move_horizontally(
     &mut self,
     distance: i16,
   ) {
-    self.set_x(self.bounding_box.x() + distance);
+    self.previous_position = self.bounding_box.position;
+    self.bounding_box.set_x(self.bounding_box.x() + distance);
   }
 
   pub fn right(&self) -> i16 {
@@ -380,19 +1372,19 @@ impl Image {
     &mut self,
     x: i16,
   ) {
-    self.bounding_box.set_x(x);
+    self.previous_position.x += x - self.bounding_box.x();
     self.bounding_box.set_x(x);
   }
 }
 
 pub struct SpriteSheet {
-  image: HtmlImageElement,
+  image: ImageElement,
   sheet: Sheet,
 }
 
 impl SpriteSheet {
   pub fn new(
-    image: HtmlImageElement,
+    image: ImageElement,
     sheet: Sheet,
   ) -> Self {
     SpriteSheet {
@@ -410,7 +1402,7 @@ impl SpriteSheet {
 
   pub fn draw(
     &self,
-    renderer: &Renderer,
+    renderer: &dyn Renderer,
     source: &Rect,
     destination: &Rect,
   ) {
@@ -420,45 +1412,251 @@ impl SpriteSheet {
 
 #[derive(Clone)]
 pub struct Audio {
-  context: AudioContext,
+  context: Option<AudioContext>,
+  gain: Option<GainNode>,
 }
 
 impl Audio {
   pub fn new() -> Result<Self> {
+    let context = sound::create_audio_context()?;
+    let gain = sound::create_gain_node(&context)?;
     Ok(Audio {
-      context: sound::create_audio_context()?,
+      context: Some(context),
+      gain: Some(gain),
     })
   }
 
+  pub fn headless() -> Self {
+    Audio {
+      context: None,
+      gain: None,
+    }
+  }
+
   pub async fn load_sound(
     &self,
     filename: &str,
   ) -> Result<Sound> {
+    let Some(context) = &self.context else {
+      return Ok(Sound::headless());
+    };
     let array_buffer: ArrayBuffer =
       browser::fetch_array_buffer(filename).await?;
     let audio_buffer: AudioBuffer =
-      sound::decode_audio_data(&self.context, &array_buffer).await?;
+      sound::decode_audio_data(context, &array_buffer).await?;
     Ok(Sound {
-      buffer: audio_buffer,
+      buffer: Some(audio_buffer),
     })
   }
 
+  pub fn pause(&self) -> Result<()> {
+    match &self.context {
+      Some(context) => sound::suspend_audio_context(context),
+      None => Ok(()),
+    }
+  }
+
   pub fn play_looping_sound(
     &self,
     sound: &Sound,
   ) -> Result<()> {
-    sound::play_sound(&self.context, &sound.buffer, sound::Looping::Yes)
+    self.play(sound, sound::Looping::Yes)
+  }
+
+  fn play(
+    &self,
+    sound: &Sound,
+    looping: sound::Looping,
+  ) -> Result<()> {
+    match (&self.context, &self.gain, &sound.buffer) {
+      (Some(context), Some(gain), Some(buffer)) => {
+        sound::play_sound(context, buffer, gain, looping)
+      },
+      _ => Ok(()),
+    }
   }
 
   pub fn play_sound(
     &self,
     sound: &Sound,
   ) -> Result<()> {
-    sound::play_sound(&self.context, &sound.buffer, sound::Looping::No)
+    self.play(sound, sound::Looping::No)
+  }
+
+  pub fn resume(&self) -> Result<()> {
+    match &self.context {
+      Some(context) => sound::resume_audio_context(context),
+      None => Ok(()),
+    }
+  }
+
+  pub fn set_volume(
+    &self,
+    volume: f32,
+  ) {
+    if let Some(gain) = &self.gain {
+      sound::set_gain(gain, volume);
+    }
   }
 }
 
 #[derive(Clone)]
 pub struct Sound {
-  buffer: AudioBuffer,
+  buffer: Option<AudioBuffer>,
+}
+
+impl Sound {
+  pub fn headless() -> Self {
+    Sound {
+      buffer: None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const OBSTACLE: Rect = Rect {
+    position: Point {
+      x: 100,
+      y: 100,
+    },
+    width: 100,
+    height: 50,
+  };
+
+  fn contact(
+    x: i16,
+    y: i16,
+    velocity_x: i16,
+    velocity_y: i16,
+  ) -> Option<Contact> {
+    Rect::new_from_x_y(x, y, 40, 40).contact(
+      &OBSTACLE,
+      &Point {
+        x: velocity_x,
+        y: velocity_y,
+      },
+    )
+  }
+
+  fn entry(
+    tick: u32,
+    code: &str,
+    pressed: bool,
+  ) -> InputLogEntry {
+    InputLogEntry {
+      tick,
+      code: code.into(),
+      pressed,
+    }
+  }
+
+  #[test]
+  fn contact_bottom_when_rising_into_the_underside() {
+    assert_eq!(
+      contact(130, 140, 8, -10),
+      Some(Contact {
+        depth: 10,
+        side: Side::Bottom,
+      })
+    );
+  }
+
+  #[test]
+  fn contact_left_when_running_into_the_side() {
+    assert_eq!(
+      contact(66, 120, 8, 0),
+      Some(Contact {
+        depth: 6,
+        side: Side::Left,
+      })
+    );
+  }
+
+  #[test]
+  fn contact_left_when_falling_slower_than_running() {
+    assert_eq!(
+      contact(66, 80, 8, 2),
+      Some(Contact {
+        depth: 6,
+        side: Side::Left,
+      })
+    );
+  }
+
+  #[test]
+  fn contact_none_when_apart() {
+    assert_eq!(contact(0, 0, 8, 8), None);
+  }
+
+  #[test]
+  fn contact_right_when_knocked_back_into_the_side() {
+    assert_eq!(
+      contact(197, 120, -3, 0),
+      Some(Contact {
+        depth: 3,
+        side: Side::Right,
+      })
+    );
+  }
+
+  #[test]
+  fn contact_top_when_landing() {
+    assert_eq!(
+      contact(130, 75, 8, 20),
+      Some(Contact {
+        depth: 15,
+        side: Side::Top,
+      })
+    );
+  }
+
+  #[test]
+  fn contact_uses_the_shallowest_side_without_velocity() {
+    assert_eq!(
+      contact(130, 62, 0, 0),
+      Some(Contact {
+        depth: 2,
+        side: Side::Top,
+      })
+    );
+    assert_eq!(
+      contact(198, 120, 0, 0),
+      Some(Contact {
+        depth: 2,
+        side: Side::Right,
+      })
+    );
+  }
+
+  #[test]
+  fn record_plays_out_like_a_replay_across_pauses() {
+    // Space is held through the first pause and pressed during the second
+    let mut keyboard = InputReplay::new(InputLog {
+      entries: vec![
+        entry(0, "ArrowRight", true),
+        entry(10, "Space", true),
+        entry(40, "Space", false),
+        entry(80, "Space", true),
+        entry(110, "Space", false),
+      ],
+    });
+    let paused =
+      |tick: u32| (20..30).contains(&tick) || (75..85).contains(&tick);
+    let mut recorder = InputRecorder::new();
+    let live: Vec<KeyState> = (0..120)
+      .map(|tick| (tick, keyboard.next_tick().clone()))
+      .filter(|(tick, _keystate)| !paused(*tick))
+      .map(|(_tick, keystate)| recorder.record(&keystate).clone())
+      .collect();
+    let mut replay = InputReplay::new(recorder.log().clone());
+    live.iter().for_each(|keystate| {
+      let replayed = replay.next_tick();
+      assert_eq!(keystate.just_pressed_keys, replayed.just_pressed_keys);
+      assert_eq!(keystate.just_released_keys, replayed.just_released_keys);
+      assert_eq!(keystate.pressed_keys, replayed.pressed_keys);
+    });
+  }
 }
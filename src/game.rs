@@ -1,28 +1,74 @@
 use std::rc::Rc;
 
 use self::red_hat_boy_states::*;
-use crate::browser::{self};
+use crate::browser::{self, HttpStatus};
+use crate::controls::{Action, Bindings};
+use crate::dog::Dog;
 use crate::engine::{
-  self, Audio, Cell, Game, Image, KeyState, Point, Rect, Renderer, Sheet,
-  Sound, SpriteSheet,
+  self, Audio, Cell, Contact, Game, Image, ImageElement, InputLog,
+  InputRecorder, InputReplay, KeyState, Point, Rect, Renderer, Sheet, Side,
+  Sound, SpriteSheet, VirtualButton,
 };
-use crate::segments::platform_and_stone;
-use crate::segments::stone_and_platform;
+use crate::leaderboard::{self, Score, Submission};
+use crate::segments::{
+  self, Difficulty, Segment, SegmentHistory, SegmentRegistry,
+};
+use crate::settings::{Settings, DEFAULT_LIVES, MAX_LIVES};
+use crate::stats::Stats;
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use futures::channel::mpsc::UnboundedReceiver;
+use futures::Future;
 use rand::prelude::*;
+use rand::rngs::StdRng;
 use rand::thread_rng;
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::JsValue;
-use web_sys::HtmlImageElement;
 
-const HEIGHT: i16 = 600;
+const BLINK_FRAMES: u8 = 4;
+const COLLECTIBLE_TICKS_PER_FRAME: u8 = 4;
+const DIFFICULTY_CURVE: DifficultyCurve = DifficultyCurve {
+  final_easy_weight_percent: 50,
+  final_hard_weight_percent: 200,
+  final_obstacle_buffer: 0,
+  final_speed_bonus: 4,
+  initial_easy_weight_percent: 100,
+  initial_hard_weight_percent: 50,
+  initial_obstacle_buffer: OBSTACLE_BUFFER,
+  ramp_meters: 500,
+};
+const DISTANCE_PER_METER: u32 = 50;
+const DOUBLE_JUMP_AURA_COLOR: &str = "rgba(255, 220, 60, 0.5)";
+const DOUBLE_JUMP_AURA_RADIUS: i16 = 20;
+pub const HEIGHT: i16 = 600;
+const HITBOX_COLOR: &str = "rgba(255, 0, 0, 0.4)";
+const HUD_COLOR: &str = "white";
+const HUD_FONT: &str = "24px 'Ken Future'";
+const HUD_LINE_HEIGHT: i16 = 30;
+const HUD_MARGIN: i16 = 10;
+const MAGNET_AURA_COLOR: &str = "rgba(255, 80, 80, 0.12)";
+const MAGNET_RADIUS: i16 = 150;
+const MAGNET_SPEED: i16 = 8;
+const NO_INTERPOLATION: f32 = 1.0;
 const OBSTACLE_BUFFER: i16 = 20;
+const POINTS_PER_OBSTACLE: u32 = 10;
+const POWER_UP_WARNING_TICKS: u16 = 120;
+const PUSH_BACK_MAX_DEPTH: i16 = 16;
+const SHIELD_AURA_COLOR: &str = "rgba(80, 160, 255, 0.35)";
+const SHIELD_AURA_MARGIN: i16 = 10;
+const SPARKLE_FRAME_NAME: &str = "Sparkle";
+const SPARKLE_FRAMES: u8 = 5;
+const TICKS_PER_SECOND: u16 = 60;
 const TIMELINE_MINIMUM: i16 = 1000;
+const VIRTUAL_BUTTON_SIZE: i16 = 100;
+const VOLUME_STEP: f32 = 0.1;
+const WIDTH: i16 = 600;
 
 #[derive(Clone)]
 enum RedHatBoyStateMachine {
+  Airborne(RedHatBoyState<Airborne>),
   Falling(RedHatBoyState<Falling>),
+  Hurt(RedHatBoyState<Hurt>),
   Idle(RedHatBoyState<Idle>),
   Jumping(RedHatBoyState<Jumping>),
   KnockedOut(RedHatBoyState<KnockedOut>),
@@ -31,9 +77,13 @@ enum RedHatBoyStateMachine {
 }
 
 pub enum Event {
+  Bump(i16),
   Jump,
   KnockOut,
   Land(i16),
+  PowerUp(PowerUp),
+  PushBack(i16),
+  ReleaseJump,
   Run,
   Slide,
   Update,
@@ -43,13 +93,20 @@ pub trait Obstacle {
   fn check_intersection(
     &self,
     boy: &mut RedHatBoy,
+    walking_speed: i16,
   );
 
   fn draw(
     &self,
-    renderer: &Renderer,
+    renderer: &dyn Renderer,
+    alpha: f32,
   );
 
+  fn intersects(
+    &self,
+    rect: &Rect,
+  ) -> bool;
+
   fn move_horizontally(
     &mut self,
     x: i16,
@@ -74,17 +131,31 @@ impl Obstacle for Barrier {
   fn check_intersection(
     &self,
     boy: &mut RedHatBoy,
+    _walking_speed: i16,
   ) {
-    if boy.bounding_box().intersects(self.image.bounding_box()) {
+    let bounding_box = self.image.bounding_box();
+    if boy
+      .hitboxes()
+      .iter()
+      .any(|hitbox| hitbox.intersects(bounding_box))
+    {
       boy.knock_out()
     }
   }
 
   fn draw(
     &self,
-    renderer: &Renderer,
+    renderer: &dyn Renderer,
+    alpha: f32,
   ) {
-    self.image.draw(renderer);
+    self.image.draw(renderer, alpha);
+  }
+
+  fn intersects(
+    &self,
+    rect: &Rect,
+  ) -> bool {
+    self.image.bounding_box().intersects(rect)
   }
 
   fn move_horizontally(
@@ -99,16 +170,384 @@ impl Obstacle for Barrier {
   }
 }
 
+pub struct Beam {
+  tiles: Tiles,
+}
+
+impl Beam {
+  pub fn new(
+    bounding_boxes: &[Rect],
+    position: Point,
+    sheet: Rc<SpriteSheet>,
+    sprite_names: &[&str],
+  ) -> Self {
+    Beam {
+      tiles: Tiles::new(
+        bounding_boxes,
+        Layout::Vertical,
+        position,
+        sheet,
+        sprite_names,
+      ),
+    }
+  }
+}
+
+impl Obstacle for Beam {
+  fn check_intersection(
+    &self,
+    boy: &mut RedHatBoy,
+    walking_speed: i16,
+  ) {
+    let velocity = boy.relative_velocity(walking_speed);
+    if let Some((_bounding_box, contact)) =
+      self.tiles.contact(&boy.hitboxes(), &velocity)
+    {
+      match contact.side {
+        Side::Bottom => boy.bump(contact.depth),
+        _ => boy.knock_out(),
+      }
+    }
+  }
+
+  fn draw(
+    &self,
+    renderer: &dyn Renderer,
+    alpha: f32,
+  ) {
+    self.tiles.draw(renderer, alpha);
+  }
+
+  fn intersects(
+    &self,
+    rect: &Rect,
+  ) -> bool {
+    self.tiles.intersects(rect)
+  }
+
+  fn move_horizontally(
+    &mut self,
+    x: i16,
+  ) {
+    self.tiles.move_horizontally(x);
+  }
+
+  fn right(&self) -> i16 {
+    self.tiles.right()
+  }
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CollectibleKind {
+  Coin,
+  DoubleJump,
+  Gem,
+  Magnet,
+  Shield,
+}
+
+impl CollectibleKind {
+  const ALL: [CollectibleKind; 5] = [
+    CollectibleKind::Coin,
+    CollectibleKind::DoubleJump,
+    CollectibleKind::Gem,
+    CollectibleKind::Magnet,
+    CollectibleKind::Shield,
+  ];
+
+  fn frame_name(&self) -> &str {
+    match self {
+      CollectibleKind::Coin => "Coin",
+      CollectibleKind::DoubleJump => "DoubleJump",
+      CollectibleKind::Gem => "Gem",
+      CollectibleKind::Magnet => "Magnet",
+      CollectibleKind::Shield => "Shield",
+    }
+  }
+
+  fn frames(&self) -> u8 {
+    match self {
+      CollectibleKind::Coin => 6,
+      _ => 2,
+    }
+  }
+
+  fn points(&self) -> u32 {
+    match self {
+      CollectibleKind::Coin => 5,
+      CollectibleKind::Gem => 25,
+      _ => 0,
+    }
+  }
+
+  fn power_up(&self) -> Option<PowerUp> {
+    match self {
+      CollectibleKind::DoubleJump => Some(PowerUp::DoubleJump),
+      CollectibleKind::Magnet => Some(PowerUp::Magnet),
+      CollectibleKind::Shield => Some(PowerUp::Shield),
+      _ => None,
+    }
+  }
+}
+
+pub struct Collectible {
+  frame: u8,
+  kind: CollectibleKind,
+  position: Point,
+  previous_position: Point,
+  sheet: Rc<SpriteSheet>,
+  sparkle: Option<u8>,
+}
+
+impl Collectible {
+  pub fn new(
+    kind: CollectibleKind,
+    position: Point,
+    sheet: Rc<SpriteSheet>,
+  ) -> Self {
+    Collectible {
+      frame: 0,
+      kind,
+      position,
+      previous_position: position,
+      sheet,
+      sparkle: None,
+    }
+  }
+
+  fn attract(
+    &mut self,
+    target: &Point,
+  ) {
+    if self.sparkle.is_some() {
+      return;
+    }
+    let bounding_box = self.bounding_box();
+    let dx = target.x - (bounding_box.x() + bounding_box.width / 2);
+    let dy = target.y - (bounding_box.y() + bounding_box.height / 2);
+    if i32::from(dx).pow(2) + i32::from(dy).pow(2)
+      > i32::from(MAGNET_RADIUS).pow(2)
+    {
+      return;
+    }
+    self.position.x += dx.clamp(-MAGNET_SPEED, MAGNET_SPEED);
+    self.position.y += dy.clamp(-MAGNET_SPEED, MAGNET_SPEED);
+  }
+
+  fn bounding_box(&self) -> Rect {
+    let cell = self.current_cell();
+    Rect::new_from_x_y(
+      self.position.x + cell.sprite_source_size.x,
+      self.position.y + cell.sprite_source_size.y,
+      cell.frame.w,
+      cell.frame.h,
+    )
+  }
+
+  fn collect(
+    &mut self,
+    boy: &RedHatBoy,
+  ) -> Option<CollectibleKind> {
+    if self.sparkle.is_some()
+      || !boy.bounding_box().intersects(&self.bounding_box())
+    {
+      return None;
+    }
+    self.sparkle = Some(0);
+    Some(self.kind)
+  }
+
+  fn current_cell(&self) -> &Cell {
+    self.sheet.cell(&self.frame_name()).expect("Cell not found")
+  }
+
+  fn draw(
+    &self,
+    renderer: &dyn Renderer,
+    alpha: f32,
+  ) {
+    let position = self.previous_position.interpolate(&self.position, alpha);
+    let cell = self.current_cell();
+    self.sheet.draw(
+      renderer,
+      &Rect::new_from_x_y(
+        cell.frame.x,
+        cell.frame.y,
+        cell.frame.w,
+        cell.frame.h,
+      ),
+      &Rect::new_from_x_y(
+        position.x + cell.sprite_source_size.x,
+        position.y + cell.sprite_source_size.y,
+        cell.frame.w,
+        cell.frame.h,
+      ),
+    );
+  }
+
+  fn finished(&self) -> bool {
+    self.sparkle.is_some_and(|sparkle| {
+      sparkle >= SPARKLE_FRAMES * COLLECTIBLE_TICKS_PER_FRAME
+    })
+  }
+
+  fn frame_name(&self) -> String {
+    match self.sparkle {
+      Some(sparkle) => format!(
+        "{} ({}).png",
+        SPARKLE_FRAME_NAME,
+        (sparkle / COLLECTIBLE_TICKS_PER_FRAME).min(SPARKLE_FRAMES - 1) + 1
+      ),
+      None => format!(
+        "{} ({}).png",
+        self.kind.frame_name(),
+        self.frame / COLLECTIBLE_TICKS_PER_FRAME + 1
+      ),
+    }
+  }
+
+  fn move_horizontally(
+    &mut self,
+    x: i16,
+  ) {
+    self.previous_position = self.position;
+    self.position.x += x;
+  }
+
+  fn right(&self) -> i16 {
+    self.bounding_box().right()
+  }
+
+  fn update(&mut self) {
+    self.frame =
+      (self.frame + 1) % (self.kind.frames() * COLLECTIBLE_TICKS_PER_FRAME);
+    if let Some(sparkle) = &mut self.sparkle {
+      *sparkle += 1;
+    }
+  }
+
+  fn validate_sheet(sheet: &SpriteSheet) -> Result<()> {
+    let mut names: Vec<String> = CollectibleKind::ALL
+      .iter()
+      .flat_map(|kind| {
+        (1..=kind.frames())
+          .map(|frame| format!("{} ({}).png", kind.frame_name(), frame))
+      })
+      .collect();
+    names.extend(
+      (1..=SPARKLE_FRAMES)
+        .map(|frame| format!("{} ({}).png", SPARKLE_FRAME_NAME, frame)),
+    );
+    names.iter().try_for_each(|name| {
+      sheet
+        .cell(name)
+        .map(|_cell| ())
+        .ok_or_else(|| anyhow!("Pickup sheet is missing {}", name))
+    })
+  }
+}
+
 pub struct Platform {
+  push_back: bool,
+  tiles: Tiles,
+}
+
+impl Platform {
+  pub fn new(
+    bounding_boxes: &[Rect],
+    position: Point,
+    push_back: bool,
+    sheet: Rc<SpriteSheet>,
+    sprite_names: &[&str],
+  ) -> Self {
+    Platform {
+      push_back,
+      tiles: Tiles::new(
+        bounding_boxes,
+        Layout::Horizontal,
+        position,
+        sheet,
+        sprite_names,
+      ),
+    }
+  }
+}
+
+impl Obstacle for Platform {
+  fn check_intersection(
+    &self,
+    boy: &mut RedHatBoy,
+    walking_speed: i16,
+  ) {
+    let boy_box = boy.bounding_box();
+    let velocity = boy.relative_velocity(walking_speed);
+    if let Some((box_to_land_on, contact)) =
+      self.tiles.contact(&boy.hitboxes(), &velocity)
+    {
+      let push_back = self.push_back && contact.depth <= PUSH_BACK_MAX_DEPTH;
+      match contact.side {
+        _ if boy.standing_on(box_to_land_on) => boy.land_on(box_to_land_on.y()),
+        Side::Bottom => boy.bump(contact.depth),
+        Side::Left if push_back && boy.can_push_back(-contact.depth) => {
+          boy.push_back(-contact.depth)
+        },
+        Side::Right if push_back => boy.push_back(contact.depth),
+        Side::Top if boy_box.y() < box_to_land_on.y() => {
+          boy.land_on(box_to_land_on.y())
+        },
+        _ => boy.knock_out(),
+      }
+    }
+  }
+
+  fn draw(
+    &self,
+    renderer: &dyn Renderer,
+    alpha: f32,
+  ) {
+    self.tiles.draw(renderer, alpha);
+  }
+
+  fn intersects(
+    &self,
+    rect: &Rect,
+  ) -> bool {
+    self.tiles.intersects(rect)
+  }
+
+  fn move_horizontally(
+    &mut self,
+    x: i16,
+  ) {
+    self.tiles.move_horizontally(x);
+  }
+
+  fn right(&self) -> i16 {
+    self.tiles.right()
+  }
+}
+
+#[derive(Clone, Copy)]
+enum Layout {
+  Horizontal,
+  Vertical,
+}
+
+// Sprites laid out in a row or column with bounding boxes that move together
+struct Tiles {
   bounding_boxes: Vec<Rect>,
+  layout: Layout,
   position: Point,
+  previous_position: Point,
   sheet: Rc<SpriteSheet>,
   sprites: Vec<Cell>,
 }
 
-impl Platform {
-  pub fn new(
+impl Tiles {
+  fn new(
     bounding_boxes: &[Rect],
+    layout: Layout,
     position: Point,
     sheet: Rc<SpriteSheet>,
     sprite_names: &[&str],
@@ -128,42 +567,37 @@ impl Platform {
         )
       })
       .collect();
-    Platform {
+    Tiles {
       bounding_boxes,
+      layout,
       position,
+      previous_position: position,
       sheet,
       sprites,
     }
   }
 
-  fn bounding_boxes(&self) -> &Vec<Rect> {
-    &self.bounding_boxes
-  }
-}
-
-impl Obstacle for Platform {
-  fn check_intersection(
+  fn contact(
     &self,
-    boy: &mut RedHatBoy,
-  ) {
-    if let Some(box_to_land_on) = self
-      .bounding_boxes()
-      .iter()
-      .find(|&bounding_box| boy.bounding_box().intersects(bounding_box))
-    {
-      if boy.velocity_y() > 0 && boy.pos_y() < self.position.y {
-        boy.land_on(box_to_land_on.y());
-      } else {
-        boy.knock_out();
-      }
-    }
+    hitboxes: &[Rect],
+    velocity: &Point,
+  ) -> Option<(&Rect, Contact)> {
+    hitboxes.iter().find_map(|hitbox| {
+      self.bounding_boxes.iter().find_map(|bounding_box| {
+        hitbox
+          .contact(bounding_box, velocity)
+          .map(|contact| (bounding_box, contact))
+      })
+    })
   }
 
   fn draw(
     &self,
-    renderer: &Renderer,
+    renderer: &dyn Renderer,
+    alpha: f32,
   ) {
-    let mut x = 0;
+    let mut position =
+      self.previous_position.interpolate(&self.position, alpha);
     self.sprites.iter().for_each(|sprite| {
       self.sheet.draw(
         renderer,
@@ -174,20 +608,34 @@ impl Obstacle for Platform {
           sprite.frame.h,
         ),
         &Rect::new_from_x_y(
-          self.position.x + x,
-          self.position.y,
+          position.x,
+          position.y,
           sprite.frame.w,
           sprite.frame.h,
         ),
       );
-      x += sprite.frame.w;
+      match self.layout {
+        Layout::Horizontal => position.x += sprite.frame.w,
+        Layout::Vertical => position.y += sprite.frame.h,
+      }
     });
   }
 
+  fn intersects(
+    &self,
+    rect: &Rect,
+  ) -> bool {
+    self
+      .bounding_boxes
+      .iter()
+      .any(|bounding_box| bounding_box.intersects(rect))
+  }
+
   fn move_horizontally(
     &mut self,
     x: i16,
   ) {
+    self.previous_position = self.position;
     self.position.x += x;
     self.bounding_boxes.iter_mut().for_each(|bounding_box| {
       bounding_box.set_x(bounding_box.position.x + x);
@@ -196,17 +644,20 @@ impl Obstacle for Platform {
 
   fn right(&self) -> i16 {
     self
-      .bounding_boxes()
-      .last()
-      .unwrap_or(&Rect::default())
-      .right()
+      .bounding_boxes
+      .iter()
+      .map(Rect::right)
+      .max()
+      .unwrap_or(self.position.x)
   }
 }
 
 impl RedHatBoyStateMachine {
   fn context(&self) -> &RedHatBoyContext {
     match self {
+      RedHatBoyStateMachine::Airborne(state) => state.context(),
       RedHatBoyStateMachine::Falling(state) => state.context(),
+      RedHatBoyStateMachine::Hurt(state) => state.context(),
       RedHatBoyStateMachine::Idle(state) => state.context(),
       RedHatBoyStateMachine::Jumping(state) => state.context(),
       RedHatBoyStateMachine::KnockedOut(state) => state.context(),
@@ -217,7 +668,9 @@ impl RedHatBoyStateMachine {
 
   fn frame_name(&self) -> &str {
     match self {
+      RedHatBoyStateMachine::Airborne(state) => state.frame_name(),
       RedHatBoyStateMachine::Falling(state) => state.frame_name(),
+      RedHatBoyStateMachine::Hurt(state) => state.frame_name(),
       RedHatBoyStateMachine::Idle(state) => state.frame_name(),
       RedHatBoyStateMachine::Jumping(state) => state.frame_name(),
       RedHatBoyStateMachine::KnockedOut(state) => state.frame_name(),
@@ -230,11 +683,61 @@ impl RedHatBoyStateMachine {
     matches!(self, RedHatBoyStateMachine::KnockedOut(_))
   }
 
+  fn map_context(
+    self,
+    f: impl FnOnce(RedHatBoyContext) -> RedHatBoyContext,
+  ) -> Self {
+    match self {
+      RedHatBoyStateMachine::Airborne(state) => state.map_context(f).into(),
+      RedHatBoyStateMachine::Falling(state) => state.map_context(f).into(),
+      RedHatBoyStateMachine::Hurt(state) => state.map_context(f).into(),
+      RedHatBoyStateMachine::Idle(state) => state.map_context(f).into(),
+      RedHatBoyStateMachine::Jumping(state) => state.map_context(f).into(),
+      RedHatBoyStateMachine::KnockedOut(state) => state.map_context(f).into(),
+      RedHatBoyStateMachine::Running(state) => state.map_context(f).into(),
+      RedHatBoyStateMachine::Sliding(state) => state.map_context(f).into(),
+    }
+  }
+
   fn transition(
     self,
     event: Event,
   ) -> Self {
     match (self.clone(), event) {
+      (machine, Event::KnockOut) if machine.context().invulnerable > 0 => {
+        machine
+      },
+      (machine, Event::KnockOut)
+        if machine.context().power_ups.remaining(PowerUp::Shield) > 0 =>
+      {
+        machine.map_context(RedHatBoyContext::absorb_hit)
+      },
+      (machine, Event::Bump(depth)) => {
+        machine.map_context(|context| context.bump(depth))
+      },
+      (machine, Event::PowerUp(power_up)) => {
+        machine.map_context(|context| context.power_up(power_up))
+      },
+      (machine, Event::PushBack(x)) => {
+        machine.map_context(|context| context.push_back(x))
+      },
+      (RedHatBoyStateMachine::Airborne(state), Event::Jump)
+        if state.context().footing().can_jump() =>
+      {
+        state.jump().into()
+      },
+      (RedHatBoyStateMachine::Airborne(state), Event::Jump) => {
+        state.buffer_jump().into()
+      },
+      (RedHatBoyStateMachine::Airborne(state), Event::KnockOut) => {
+        state.knock_out().into()
+      },
+      (RedHatBoyStateMachine::Airborne(state), Event::Land(position)) => {
+        state.land_on(position).into()
+      },
+      (RedHatBoyStateMachine::Airborne(state), Event::Update) => {
+        state.update().into()
+      },
       (RedHatBoyStateMachine::Idle(state), Event::Run) => state.run().into(),
       (RedHatBoyStateMachine::Idle(state), Event::Update) => {
         state.update().into()
@@ -242,12 +745,29 @@ impl RedHatBoyStateMachine {
       (RedHatBoyStateMachine::Falling(state), Event::Update) => {
         state.update().into()
       },
+      (RedHatBoyStateMachine::Hurt(state), Event::Land(position)) => {
+        state.land_on(position).into()
+      },
+      (RedHatBoyStateMachine::Hurt(state), Event::Update) => {
+        state.update().into()
+      },
+      (RedHatBoyStateMachine::Jumping(state), Event::Jump)
+        if state.context().can_double_jump() =>
+      {
+        state.double_jump().into()
+      },
+      (RedHatBoyStateMachine::Jumping(state), Event::Jump) => {
+        state.buffer_jump().into()
+      },
       (RedHatBoyStateMachine::Jumping(state), Event::KnockOut) => {
         state.knock_out().into()
       },
       (RedHatBoyStateMachine::Jumping(state), Event::Land(position)) => {
         state.land_on(position).into()
       },
+      (RedHatBoyStateMachine::Jumping(state), Event::ReleaseJump) => {
+        state.release_jump().into()
+      },
       (RedHatBoyStateMachine::Jumping(state), Event::Update) => {
         state.update().into()
       },
@@ -275,6 +795,9 @@ impl RedHatBoyStateMachine {
       (RedHatBoyStateMachine::Sliding(state), Event::Land(position)) => {
         state.land_on(position).into()
       },
+      (RedHatBoyStateMachine::Sliding(state), Event::Slide) => {
+        state.slide().into()
+      },
       (RedHatBoyStateMachine::Sliding(state), Event::Update) => {
         state.update().into()
       },
@@ -287,6 +810,15 @@ impl RedHatBoyStateMachine {
   }
 }
 
+impl From<AirborneEndState> for RedHatBoyStateMachine {
+  fn from(end_state: AirborneEndState) -> Self {
+    match end_state {
+      AirborneEndState::Airborne(airborne_state) => airborne_state.into(),
+      AirborneEndState::Landing(landing_state) => landing_state.into(),
+    }
+  }
+}
+
 impl From<FallingEndState> for RedHatBoyStateMachine {
   fn from(end_state: FallingEndState) -> Self {
     match end_state {
@@ -296,28 +828,67 @@ impl From<FallingEndState> for RedHatBoyStateMachine {
   }
 }
 
+impl From<HurtEndState> for RedHatBoyStateMachine {
+  fn from(end_state: HurtEndState) -> Self {
+    match end_state {
+      HurtEndState::Complete(running_state) => running_state.into(),
+      HurtEndState::Hurt(hurt_state) => hurt_state.into(),
+    }
+  }
+}
+
 impl From<JumpingEndState> for RedHatBoyStateMachine {
   fn from(end_state: JumpingEndState) -> Self {
     match end_state {
       JumpingEndState::Jumping(jumping_state) => jumping_state.into(),
-      JumpingEndState::Landing(running_state) => running_state.into(),
+      JumpingEndState::Landing(landing_state) => landing_state.into(),
     }
   }
 }
 
-impl From<RedHatBoyState<Falling>> for RedHatBoyStateMachine {
-  fn from(state: RedHatBoyState<Falling>) -> Self {
-    RedHatBoyStateMachine::Falling(state)
+impl From<LandingEndState> for RedHatBoyStateMachine {
+  fn from(end_state: LandingEndState) -> Self {
+    match end_state {
+      LandingEndState::Jumping(jumping_state) => jumping_state.into(),
+      LandingEndState::Running(running_state) => running_state.into(),
+    }
   }
 }
 
-impl From<RedHatBoyState<Idle>> for RedHatBoyStateMachine {
-  fn from(state: RedHatBoyState<Idle>) -> Self {
-    RedHatBoyStateMachine::Idle(state)
+impl From<RedHatBoyState<Airborne>> for RedHatBoyStateMachine {
+  fn from(state: RedHatBoyState<Airborne>) -> Self {
+    RedHatBoyStateMachine::Airborne(state)
   }
 }
 
-impl From<RedHatBoyState<Jumping>> for RedHatBoyStateMachine {
+impl From<RedHatBoyState<Falling>> for RedHatBoyStateMachine {
+  fn from(state: RedHatBoyState<Falling>) -> Self {
+    RedHatBoyStateMachine::Falling(state)
+  }
+}
+
+impl From<KnockOutEndState> for RedHatBoyStateMachine {
+  fn from(end_state: KnockOutEndState) -> Self {
+    match end_state {
+      KnockOutEndState::Falling(falling_state) => falling_state.into(),
+      KnockOutEndState::Hurt(hurt_state) => hurt_state.into(),
+    }
+  }
+}
+
+impl From<RedHatBoyState<Hurt>> for RedHatBoyStateMachine {
+  fn from(state: RedHatBoyState<Hurt>) -> Self {
+    RedHatBoyStateMachine::Hurt(state)
+  }
+}
+
+impl From<RedHatBoyState<Idle>> for RedHatBoyStateMachine {
+  fn from(state: RedHatBoyState<Idle>) -> Self {
+    RedHatBoyStateMachine::Idle(state)
+  }
+}
+
+impl From<RedHatBoyState<Jumping>> for RedHatBoyStateMachine {
   fn from(state: RedHatBoyState<Jumping>) -> Self {
     RedHatBoyStateMachine::Jumping(state)
   }
@@ -341,6 +912,15 @@ impl From<RedHatBoyState<Sliding>> for RedHatBoyStateMachine {
   }
 }
 
+impl From<RunningEndState> for RedHatBoyStateMachine {
+  fn from(end_state: RunningEndState) -> Self {
+    match end_state {
+      RunningEndState::Airborne(airborne_state) => airborne_state.into(),
+      RunningEndState::Running(running_state) => running_state.into(),
+    }
+  }
+}
+
 impl From<SlidingEndState> for RedHatBoyStateMachine {
   fn from(end_state: SlidingEndState) -> Self {
     match end_state {
@@ -351,37 +931,52 @@ impl From<SlidingEndState> for RedHatBoyStateMachine {
 }
 
 pub struct RedHatBoy {
+  previous_position: Point,
   state_machine: RedHatBoyStateMachine,
   sprite_sheet: Sheet,
-  image: HtmlImageElement,
+  image: ImageElement,
 }
 
 impl RedHatBoy {
   fn new(
     audio: Audio,
-    image: HtmlImageElement,
+    image: ImageElement,
     jump_sound: Sound,
+    lives: u8,
     sheet: Sheet,
   ) -> Self {
+    let state_machine = RedHatBoyStateMachine::Idle(RedHatBoyState::new(
+      audio, jump_sound, lives,
+    ));
     RedHatBoy {
-      state_machine: RedHatBoyStateMachine::Idle(RedHatBoyState::new(
-        audio, jump_sound,
-      )),
+      previous_position: state_machine.context().position,
+      state_machine,
       sprite_sheet: sheet,
       image,
     }
   }
 
   fn bounding_box(&self) -> Rect {
-    const X_OFFSET: i16 = 18;
-    const Y_OFFSET: i16 = 14;
-    const WIDTH_OFFSET: i16 = 28;
-    let mut bounding_box = self.destination_box();
-    bounding_box.position.x += X_OFFSET;
-    bounding_box.width -= WIDTH_OFFSET;
-    bounding_box.position.y += Y_OFFSET;
-    bounding_box.height -= Y_OFFSET;
-    bounding_box
+    self
+      .hitboxes()
+      .into_iter()
+      .reduce(|bounding_box, hitbox| bounding_box.union(&hitbox))
+      .unwrap_or_else(|| self.destination_box())
+  }
+
+  fn can_push_back(
+    &self,
+    x: i16,
+  ) -> bool {
+    self.state_machine.context().can_push_back(x)
+  }
+
+  fn center(&self) -> Point {
+    let bounding_box = self.bounding_box();
+    Point {
+      x: bounding_box.x() + bounding_box.width / 2,
+      y: bounding_box.y() + bounding_box.height / 2,
+    }
   }
 
   fn current_sprite(&self) -> Option<&Cell> {
@@ -389,13 +984,18 @@ impl RedHatBoy {
   }
 
   fn destination_box(&self) -> Rect {
+    self.destination_box_at(&self.state_machine.context().position)
+  }
+
+  fn destination_box_at(
+    &self,
+    position: &Point,
+  ) -> Rect {
     let sprite = self.current_sprite().expect("Cell not found");
     Rect {
       position: Point {
-        x: self.state_machine.context().position.x
-          + sprite.sprite_source_size.x,
-        y: self.state_machine.context().position.y
-          + sprite.sprite_source_size.y,
+        x: position.x + sprite.sprite_source_size.x,
+        y: position.y + sprite.sprite_source_size.y,
       },
       width: sprite.frame.w,
       height: sprite.frame.h,
@@ -404,8 +1004,16 @@ impl RedHatBoy {
 
   fn draw(
     &self,
-    renderer: &Renderer,
+    renderer: &dyn Renderer,
+    alpha: f32,
   ) {
+    let position = self
+      .previous_position
+      .interpolate(&self.state_machine.context().position, alpha);
+    self.draw_power_ups(renderer, &self.destination_box_at(&position));
+    if self.state_machine.context().invulnerable / BLINK_FRAMES % 2 == 1 {
+      return;
+    }
     let sprite = self.current_sprite().expect("Cell not found");
     renderer.draw_image(
       &self.image,
@@ -417,10 +1025,65 @@ impl RedHatBoy {
         width: sprite.frame.w,
         height: sprite.frame.h,
       },
-      &self.destination_box(),
+      &self.destination_box_at(&position),
     );
   }
 
+  fn draw_power_ups(
+    &self,
+    renderer: &dyn Renderer,
+    destination: &Rect,
+  ) {
+    let center = Point {
+      x: destination.x() + destination.width / 2,
+      y: destination.y() + destination.height / 2,
+    };
+    PowerUp::ALL.iter().for_each(|power_up| {
+      let remaining = self.power_up_remaining(*power_up);
+      if remaining == 0
+        || (remaining < POWER_UP_WARNING_TICKS
+          && remaining / u16::from(BLINK_FRAMES) % 2 == 1)
+      {
+        return;
+      }
+      match power_up {
+        PowerUp::DoubleJump => renderer.fill_circle(
+          &Point {
+            x: center.x,
+            y: destination.bottom(),
+          },
+          DOUBLE_JUMP_AURA_RADIUS,
+          DOUBLE_JUMP_AURA_COLOR,
+        ),
+        PowerUp::Magnet => {
+          renderer.fill_circle(&center, MAGNET_RADIUS, MAGNET_AURA_COLOR)
+        },
+        PowerUp::Shield => renderer.fill_circle(
+          &center,
+          destination.height / 2 + SHIELD_AURA_MARGIN,
+          SHIELD_AURA_COLOR,
+        ),
+      }
+    });
+  }
+
+  fn bump(
+    &mut self,
+    depth: i16,
+  ) {
+    self.state_machine =
+      self.state_machine.clone().transition(Event::Bump(depth));
+  }
+
+  fn draw_hitboxes(
+    &self,
+    renderer: &dyn Renderer,
+  ) {
+    self.hitboxes().iter().for_each(|hitbox| {
+      renderer.fill_rect(hitbox, HITBOX_COLOR);
+    });
+  }
+
   fn frame_name(&self) -> String {
     format!(
       "{} ({}).png",
@@ -434,6 +1097,28 @@ impl RedHatBoy {
     self.state_machine = self.state_machine.clone().transition(Event::Jump);
   }
 
+  fn hitboxes(&self) -> Vec<Rect> {
+    let position = self.state_machine.context().position;
+    self
+      .sprite_sheet
+      .hitboxes
+      .get(&self.frame_name())
+      .map(|hitboxes| {
+        hitboxes
+          .iter()
+          .map(|hitbox| {
+            Rect::new_from_x_y(
+              position.x + hitbox.x,
+              position.y + hitbox.y,
+              hitbox.w,
+              hitbox.h,
+            )
+          })
+          .collect()
+      })
+      .unwrap_or_default()
+  }
+
   fn knock_out(&mut self) {
     self.state_machine = self.state_machine.clone().transition(Event::KnockOut);
   }
@@ -450,19 +1135,70 @@ impl RedHatBoy {
       self.state_machine.clone().transition(Event::Land(position));
   }
 
-  fn pos_y(&self) -> i16 {
-    self.state_machine.context().position.y
+  fn lives(&self) -> u8 {
+    self.state_machine.context().lives
+  }
+
+  fn power_up(
+    &mut self,
+    power_up: PowerUp,
+  ) {
+    self.state_machine = self
+      .state_machine
+      .clone()
+      .transition(Event::PowerUp(power_up));
+  }
+
+  fn power_up_remaining(
+    &self,
+    power_up: PowerUp,
+  ) -> u16 {
+    self.state_machine.context().power_ups.remaining(power_up)
+  }
+
+  fn push_back(
+    &mut self,
+    x: i16,
+  ) {
+    self.state_machine =
+      self.state_machine.clone().transition(Event::PushBack(x));
+  }
+
+  // Obstacles move at the walking speed and a grounded boy is not falling
+  fn relative_velocity(
+    &self,
+    walking_speed: i16,
+  ) -> Point {
+    let context = self.state_machine.context();
+    let y = if context.footing().grounded() {
+      context.velocity.y.min(0)
+    } else {
+      context.velocity.y
+    };
+    Point {
+      x: -walking_speed,
+      y,
+    }
   }
 
-  fn reset(boy: Self) -> Self {
+  fn reset(
+    boy: Self,
+    lives: u8,
+  ) -> Self {
     RedHatBoy::new(
       boy.state_machine.context().audio.clone(),
       boy.image,
       boy.state_machine.context().jump_sound.clone(),
+      lives,
       boy.sprite_sheet,
     )
   }
 
+  fn release_jump(&mut self) {
+    self.state_machine =
+      self.state_machine.clone().transition(Event::ReleaseJump);
+  }
+
   fn run_right(&mut self) {
     self.state_machine = self.state_machine.clone().transition(Event::Run);
   }
@@ -471,12 +1207,32 @@ impl RedHatBoy {
     self.state_machine = self.state_machine.clone().transition(Event::Slide);
   }
 
+  fn standing_on(
+    &self,
+    rect: &Rect,
+  ) -> bool {
+    self.state_machine.context().standing_on(rect.y())
+  }
+
   fn update(&mut self) {
+    self.previous_position = self.state_machine.context().position;
     self.state_machine = self.state_machine.clone().update();
   }
 
-  fn velocity_y(&self) -> i16 {
-    self.state_machine.context().velocity.y
+  fn validate_sheet(sheet: &Sheet) -> Result<()> {
+    sheet.frames.keys().try_for_each(|name| {
+      match sheet.hitboxes.get(name) {
+        Some(hitboxes) if !hitboxes.is_empty() => Ok(()),
+        _ => Err(anyhow!("Hitboxes are missing {}", name)),
+      }
+    })?;
+    sheet.hitboxes.keys().try_for_each(|name| {
+      sheet
+        .frames
+        .get(name)
+        .map(|_cell| ())
+        .ok_or_else(|| anyhow!("Hitboxes name unknown frame {}", name))
+    })
   }
 
   fn walking_speed(&self) -> i16 {
@@ -484,26 +1240,289 @@ impl RedHatBoy {
   }
 }
 
+#[derive(Clone, Copy)]
+struct DifficultyCurve {
+  final_easy_weight_percent: u32,
+  final_hard_weight_percent: u32,
+  final_obstacle_buffer: i16,
+  final_speed_bonus: i16,
+  initial_easy_weight_percent: u32,
+  initial_hard_weight_percent: u32,
+  initial_obstacle_buffer: i16,
+  ramp_meters: u32,
+}
+
+impl DifficultyCurve {
+  fn interpolate(
+    &self,
+    initial: u32,
+    end: u32,
+    meters: u32,
+  ) -> u32 {
+    let percent = self.percent(meters);
+    (initial * (100 - percent) + end * percent) / 100
+  }
+
+  fn obstacle_buffer(
+    &self,
+    meters: u32,
+  ) -> i16 {
+    let range = self.initial_obstacle_buffer - self.final_obstacle_buffer;
+    self.initial_obstacle_buffer - range * self.percent(meters) as i16 / 100
+  }
+
+  fn percent(
+    &self,
+    meters: u32,
+  ) -> u32 {
+    meters.min(self.ramp_meters) * 100 / self.ramp_meters
+  }
+
+  fn speed_bonus(
+    &self,
+    meters: u32,
+  ) -> i16 {
+    self.final_speed_bonus * self.percent(meters) as i16 / 100
+  }
+
+  fn weight_percent(
+    &self,
+    difficulty: Difficulty,
+    meters: u32,
+  ) -> u32 {
+    match difficulty {
+      Difficulty::Easy => self.interpolate(
+        self.initial_easy_weight_percent,
+        self.final_easy_weight_percent,
+        meters,
+      ),
+      Difficulty::Medium => 100,
+      Difficulty::Hard => self.interpolate(
+        self.initial_hard_weight_percent,
+        self.final_hard_weight_percent,
+        meters,
+      ),
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct Assets {
+  pub background: ImageElement,
+  pub dog_image: ImageElement,
+  pub dog_sheet: Sheet,
+  pub pickups: Rc<SpriteSheet>,
+  pub rhb_image: ImageElement,
+  pub rhb_sheet: Sheet,
+  pub segments: SegmentRegistry,
+  pub stone: ImageElement,
+  pub tiles: Rc<SpriteSheet>,
+}
+
+impl Assets {
+  pub fn validate(&self) -> Result<()> {
+    self.segments.validate(&self.tiles)?;
+    Collectible::validate_sheet(&self.pickups)?;
+    RedHatBoy::validate_sheet(&self.rhb_sheet)
+  }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Recording {
+  pub bindings: Bindings,
+  pub input_log: InputLog,
+  #[serde(default = "recording_lives")]
+  pub lives: u8,
+  pub seed: u64,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RunResult {
+  pub distance: u32,
+  pub score: u32,
+}
+
+enum InputSource {
+  Live(InputRecorder),
+  Replay(Replay),
+}
+
+struct Replay {
+  bindings: Bindings,
+  input: InputReplay,
+  lives: u8,
+}
+
+impl Replay {
+  fn new(recording: Recording) -> Self {
+    Replay {
+      bindings: recording.bindings,
+      input: InputReplay::new(recording.input_log),
+      lives: recording.lives,
+    }
+  }
+}
+
 struct Walk {
   backgrounds: [Image; 2],
+  backtracked: u32,
+  bindings: Bindings,
   boy: RedHatBoy,
+  collectibles: Vec<Collectible>,
+  difficulty: DifficultyCurve,
+  distance: u32,
+  dog: Dog,
+  input: InputSource,
+  lives: u8,
   obstacle_sheet: Rc<SpriteSheet>,
   obstacles: Vec<Box<dyn Obstacle>>,
-  stone: HtmlImageElement,
+  obstacles_cleared: u32,
+  pickup_points: u32,
+  pickup_sheet: Rc<SpriteSheet>,
+  pickup_sound: Sound,
+  pickups: u32,
+  rng: StdRng,
+  seed: u64,
+  segment_history: SegmentHistory,
+  segments: SegmentRegistry,
+  settings: Settings,
+  show_hitboxes: bool,
+  stone: ImageElement,
+  tap_jump: bool,
   timeline: i16,
 }
 
 impl Walk {
+  fn new(
+    assets: Assets,
+    audio: Audio,
+    jump_sound: Sound,
+    pickup_sound: Sound,
+    input: InputSource,
+    seed: u64,
+    settings: Settings,
+  ) -> Self {
+    let background_width = assets.background.width();
+    let backgrounds = [
+      Image::new(
+        assets.background.clone(),
+        Point {
+          x: 0,
+          y: 0,
+        },
+      ),
+      Image::new(
+        assets.background,
+        Point {
+          x: background_width,
+          y: 0,
+        },
+      ),
+    ];
+    let mut segment_history = SegmentHistory::default();
+    let start = assets.segments.start(&mut segment_history);
+    let starting_obstacles =
+      start.create(0, assets.tiles.clone(), assets.stone.clone());
+    let collectibles = start.create_collectibles(0, assets.pickups.clone());
+    let timeline = rightmost(&starting_obstacles);
+    let (bindings, lives) = match &input {
+      InputSource::Live(_) => (settings.bindings.clone(), settings.lives),
+      InputSource::Replay(replay) => (replay.bindings.clone(), replay.lives),
+    };
+    let boy = RedHatBoy::new(
+      audio,
+      assets.rhb_image,
+      jump_sound,
+      lives,
+      assets.rhb_sheet,
+    );
+    let dog = Dog::new(
+      assets.dog_image,
+      assets.dog_sheet,
+      Dog::leash_x(&boy.bounding_box()),
+    );
+    Walk {
+      backgrounds,
+      backtracked: 0,
+      bindings,
+      boy,
+      collectibles,
+      difficulty: DIFFICULTY_CURVE,
+      distance: 0,
+      dog,
+      input,
+      lives,
+      obstacle_sheet: assets.tiles,
+      obstacles: starting_obstacles,
+      obstacles_cleared: 0,
+      pickup_points: 0,
+      pickup_sheet: assets.pickups,
+      pickup_sound,
+      pickups: 0,
+      rng: StdRng::seed_from_u64(seed),
+      seed,
+      segment_history,
+      segments: assets.segments,
+      settings,
+      show_hitboxes: false,
+      stone: assets.stone,
+      tap_jump: false,
+      timeline,
+    }
+  }
+
   fn draw(
     &self,
-    renderer: &Renderer,
+    renderer: &dyn Renderer,
+    alpha: f32,
   ) {
     self.backgrounds.iter().for_each(|background| {
-      background.draw(renderer);
+      background.draw(renderer, alpha);
     });
-    self.boy.draw(renderer);
+    self.dog.draw(renderer, alpha);
+    self.boy.draw(renderer, alpha);
+    if self.show_hitboxes {
+      self.boy.draw_hitboxes(renderer);
+    }
     self.obstacles.iter().for_each(|obstacle| {
-      obstacle.draw(renderer);
+      obstacle.draw(renderer, alpha);
+    });
+    self.collectibles.iter().for_each(|collectible| {
+      collectible.draw(renderer, alpha);
+    });
+  }
+
+  fn draw_hud(
+    &self,
+    renderer: &dyn Renderer,
+  ) {
+    let mut lines = vec![
+      format!("Score: {}", self.score()),
+      format!("Distance: {}m", self.meters()),
+      format!("Lives: {}", self.boy.lives()),
+      format!("Pickups: {}", self.pickups),
+    ];
+    lines.extend(PowerUp::ALL.iter().filter_map(|power_up| {
+      let remaining = self.boy.power_up_remaining(*power_up);
+      (remaining > 0).then(|| {
+        format!(
+          "{}: {}s",
+          power_up.label(),
+          remaining.div_ceil(TICKS_PER_SECOND)
+        )
+      })
+    }));
+    lines.iter().enumerate().for_each(|(index, line)| {
+      renderer.fill_text(
+        line,
+        &Point {
+          x: HUD_MARGIN,
+          y: HUD_MARGIN + index as i16 * HUD_LINE_HEIGHT,
+        },
+        HUD_FONT,
+        HUD_COLOR,
+      );
     });
   }
 
@@ -511,42 +1530,245 @@ impl Walk {
     self.boy.knocked_out()
   }
 
-  fn generate_next_segment(&mut self) {
-    let mut rng = thread_rng();
-    let next_segment = rng.gen_range(0..2);
-    let mut next_obstacles = match next_segment {
-      0 => stone_and_platform(
-        self.timeline + OBSTACLE_BUFFER,
-        self.obstacle_sheet.clone(),
-        self.stone.clone(),
-      ),
-      1 => platform_and_stone(
-        self.timeline + OBSTACLE_BUFFER,
-        self.obstacle_sheet.clone(),
-        self.stone.clone(),
-      ),
-      _ => vec![],
+  fn audio(&self) -> &Audio {
+    &self.boy.state_machine.context().audio
+  }
+
+  fn bindings(&self) -> &Bindings {
+    &self.bindings
+  }
+
+  fn choose_segment(
+    &mut self,
+    meters: u32,
+  ) -> Segment {
+    let difficulty = self.difficulty;
+    let Some(index) = self.segments.choose(
+      &self.segment_history,
+      meters,
+      &mut self.rng,
+      |tag| difficulty.weight_percent(tag, meters),
+    ) else {
+      return self.segments.start_segment().clone();
     };
+    let segment = self.segments.segments[index].clone();
+    if !segment.procedural {
+      self.segments.record(&mut self.segment_history, index);
+      return segment;
+    }
+    let speed = RUNNING_SPEED + difficulty.speed_bonus(meters);
+    match segments::generate(&segment, &mut self.rng, &self.stone, speed) {
+      Some(generated) => {
+        self.segments.record(&mut self.segment_history, index);
+        generated
+      },
+      None => self.segments.start_segment().clone(),
+    }
+  }
+
+  fn generate_next_segment(&mut self) {
+    let meters = self.meters();
+    let offset_x = self.timeline + self.difficulty.obstacle_buffer(meters);
+    let segment = self.choose_segment(meters);
+    let mut next_obstacles =
+      segment.create(offset_x, self.obstacle_sheet.clone(), self.stone.clone());
     self.timeline = rightmost(&next_obstacles);
     self.obstacles.append(&mut next_obstacles);
+    self.collectibles.append(
+      &mut segment.create_collectibles(offset_x, self.pickup_sheet.clone()),
+    );
   }
 
   fn reset(walk: Self) -> Self {
+    let mut segment_history = SegmentHistory::default();
+    let start = walk.segments.start(&mut segment_history);
     let starting_obstacles =
-      stone_and_platform(0, walk.obstacle_sheet.clone(), walk.stone.clone());
+      start.create(0, walk.obstacle_sheet.clone(), walk.stone.clone());
+    let collectibles = start.create_collectibles(0, walk.pickup_sheet.clone());
     let timeline = rightmost(&starting_obstacles);
+    let seed = choose_seed();
+    let settings = Settings::load();
+    let boy = RedHatBoy::reset(walk.boy, settings.lives);
+    let dog = Dog::reset(walk.dog, Dog::leash_x(&boy.bounding_box()));
     Walk {
       backgrounds: walk.backgrounds,
-      boy: RedHatBoy::reset(walk.boy),
+      backtracked: 0,
+      bindings: settings.bindings.clone(),
+      boy,
+      collectibles,
+      difficulty: walk.difficulty,
+      distance: 0,
+      dog,
+      input: InputSource::Live(InputRecorder::new()),
+      lives: settings.lives,
       obstacle_sheet: walk.obstacle_sheet,
       obstacles: starting_obstacles,
+      obstacles_cleared: 0,
+      pickup_points: 0,
+      pickup_sheet: walk.pickup_sheet,
+      pickup_sound: walk.pickup_sound,
+      pickups: 0,
+      rng: StdRng::seed_from_u64(seed),
+      seed,
+      segment_history,
+      segments: walk.segments,
+      settings,
+      show_hitboxes: walk.show_hitboxes,
       stone: walk.stone,
+      tap_jump: false,
       timeline,
     }
   }
 
+  fn meters(&self) -> u32 {
+    self.distance / DISTANCE_PER_METER
+  }
+
+  fn recording(&self) -> Option<Recording> {
+    match &self.input {
+      InputSource::Live(recorder) => Some(Recording {
+        bindings: self.bindings.clone(),
+        input_log: recorder.log().clone(),
+        lives: self.lives,
+        seed: self.seed,
+      }),
+      InputSource::Replay(_) => None,
+    }
+  }
+
+  fn result(&self) -> RunResult {
+    RunResult {
+      distance: self.meters(),
+      score: self.score(),
+    }
+  }
+
+  fn score(&self) -> u32 {
+    self.meters()
+      + self.obstacles_cleared * POINTS_PER_OBSTACLE
+      + self.pickup_points
+  }
+
+  fn tick_input(
+    &mut self,
+    keystate: &KeyState,
+  ) -> KeyState {
+    match &mut self.input {
+      InputSource::Live(recorder) => recorder.record(keystate).clone(),
+      InputSource::Replay(replay) => replay.input.next_tick().clone(),
+    }
+  }
+
+  // Ground lost to knockback is covered again before distance grows
+  fn travel(
+    &mut self,
+    walking_speed: i16,
+  ) {
+    let speed = u32::from(walking_speed.unsigned_abs());
+    if walking_speed > 0 {
+      self.backtracked += speed;
+    } else {
+      let recovered = speed.min(self.backtracked);
+      self.backtracked -= recovered;
+      self.distance += speed - recovered;
+    }
+  }
+
+  fn update(
+    &mut self,
+    keystate: &KeyState,
+  ) {
+    if self.bindings().is_pressed(keystate, Action::Slide) {
+      self.boy.slide();
+    }
+    if self.bindings().just_pressed(keystate, Action::Jump) {
+      self.tap_jump = self.bindings().tapped(keystate, Action::Jump);
+      self.boy.jump();
+    } else if !self.tap_jump
+      && !self.bindings().is_pressed(keystate, Action::Jump)
+    {
+      self.boy.release_jump();
+    }
+    self.boy.update();
+    let walking_speed = self.velocity();
+    let [first_background, second_background] = &mut self.backgrounds;
+    first_background.move_horizontally(walking_speed);
+    second_background.move_horizontally(walking_speed);
+    if first_background.right() < 0 {
+      first_background.set_x(second_background.right());
+    }
+    if second_background.right() < 0 {
+      second_background.set_x(first_background.right());
+    }
+    let first_x = first_background.bounding_box().x();
+    let second_x = second_background.bounding_box().x();
+    if first_x > 0 && second_x > first_x {
+      second_background.set_x(first_x - second_background.bounding_box().width);
+    }
+    if second_x > 0 && first_x > second_x {
+      first_background.set_x(second_x - first_background.bounding_box().width);
+    }
+    self.travel(walking_speed);
+    self.obstacles.retain(|obstacle| obstacle.right() > 0);
+    let boy_left = self.boy.bounding_box().x();
+    self.obstacles.iter_mut().for_each(|obstacle| {
+      let ahead = obstacle.right() >= boy_left;
+      obstacle.move_horizontally(walking_speed);
+      obstacle.check_intersection(&mut self.boy, walking_speed);
+      if ahead && obstacle.right() < boy_left && self.backtracked == 0 {
+        self.obstacles_cleared += 1;
+      }
+    });
+    let magnet = (self.boy.power_up_remaining(PowerUp::Magnet) > 0)
+      .then(|| self.boy.center());
+    let mut collected = false;
+    for collectible in self.collectibles.iter_mut() {
+      collectible.move_horizontally(walking_speed);
+      if let Some(target) = &magnet {
+        collectible.attract(target);
+      }
+      collectible.update();
+      if let Some(kind) = collectible.collect(&self.boy) {
+        collected = true;
+        match kind.power_up() {
+          Some(power_up) => self.boy.power_up(power_up),
+          None => {
+            self.pickup_points += kind.points();
+            self.pickups += 1;
+          },
+        }
+      }
+    }
+    self
+      .collectibles
+      .retain(|collectible| !collectible.finished() && collectible.right() > 0);
+    if collected {
+      if let Err(err) = self.audio().play_sound(&self.pickup_sound) {
+        log!("Error playing pickup sound {:#?}", err);
+      }
+    }
+    if self.timeline < TIMELINE_MINIMUM {
+      self.generate_next_segment();
+    } else {
+      self.timeline += walking_speed;
+    }
+  }
+
+  fn update_dog(&mut self) {
+    self.dog.update(
+      Dog::leash_x(&self.boy.bounding_box()),
+      &self.obstacles,
+      self.boy.walking_speed() > 0,
+    );
+  }
+
   fn velocity(&self) -> i16 {
-    -self.boy.walking_speed()
+    let walking_speed = self.boy.walking_speed();
+    if walking_speed > 0 {
+      -(walking_speed + self.difficulty.speed_bonus(self.meters()))
+    } else {
+      -walking_speed
+    }
   }
 }
 
@@ -556,13 +1778,26 @@ pub struct WalkTheDog {
 
 struct Ready;
 struct Walking;
+struct Paused {
+  resume_event: UnboundedReceiver<()>,
+}
 struct GameOver {
+  lives_down_event: UnboundedReceiver<()>,
+  lives_up_event: UnboundedReceiver<()>,
   new_game_event: UnboundedReceiver<()>,
+  rebind_events: Vec<(Action, UnboundedReceiver<()>)>,
+  rebinding: Option<Action>,
+  recording: Option<Recording>,
+  reset_bindings_event: UnboundedReceiver<()>,
+  submit_score_event: Option<UnboundedReceiver<()>>,
+  volume_down_event: UnboundedReceiver<()>,
+  volume_up_event: UnboundedReceiver<()>,
 }
 
 enum WalkTheDogStateMachine {
   Ready(WalkTheDogState<Ready>),
   Walking(WalkTheDogState<Walking>),
+  Paused(WalkTheDogState<Paused>),
   GameOver(WalkTheDogState<GameOver>),
 }
 
@@ -574,9 +1809,24 @@ struct WalkTheDogState<T> {
 impl<T> WalkTheDogState<T> {
   fn draw(
     &self,
-    renderer: &Renderer,
+    renderer: &dyn Renderer,
+    alpha: f32,
   ) {
-    self.walk.draw(renderer);
+    self.walk.draw(renderer, alpha);
+  }
+
+  fn pause_audio(self) -> Self {
+    if let Err(err) = self.walk.audio().pause() {
+      log!("Error pausing audio {:#?}", err);
+    }
+    self
+  }
+
+  fn resume_audio(self) -> Self {
+    if let Err(err) = self.walk.audio().resume() {
+      log!("Error resuming audio {:#?}", err);
+    }
+    self
   }
 }
 
@@ -587,12 +1837,42 @@ impl WalkTheDogStateMachine {
 
   fn draw(
     &self,
-    renderer: &Renderer,
+    renderer: &dyn Renderer,
+    alpha: f32,
   ) {
     match self {
-      WalkTheDogStateMachine::GameOver(state) => state.draw(renderer),
-      WalkTheDogStateMachine::Ready(state) => state.draw(renderer),
-      WalkTheDogStateMachine::Walking(state) => state.draw(renderer),
+      WalkTheDogStateMachine::GameOver(state) => {
+        state.draw(renderer, NO_INTERPOLATION)
+      },
+      WalkTheDogStateMachine::Paused(state) => {
+        state.draw(renderer, NO_INTERPOLATION);
+        state.walk.draw_hud(renderer);
+      },
+      WalkTheDogStateMachine::Ready(state) => {
+        state.draw(renderer, NO_INTERPOLATION)
+      },
+      WalkTheDogStateMachine::Walking(state) => {
+        state.draw(renderer, alpha);
+        state.walk.draw_hud(renderer);
+      },
+    }
+  }
+
+  fn pause(self) -> Self {
+    match self {
+      WalkTheDogStateMachine::GameOver(state) => state.pause_audio().into(),
+      WalkTheDogStateMachine::Paused(state) => state.into(),
+      WalkTheDogStateMachine::Ready(state) => state.pause_audio().into(),
+      WalkTheDogStateMachine::Walking(state) => state.pause().into(),
+    }
+  }
+
+  fn resume(self) -> Self {
+    match self {
+      WalkTheDogStateMachine::GameOver(state) => state.resume_audio().into(),
+      WalkTheDogStateMachine::Paused(state) => state.into(),
+      WalkTheDogStateMachine::Ready(state) => state.resume_audio().into(),
+      WalkTheDogStateMachine::Walking(state) => state.into(),
     }
   }
 
@@ -601,7 +1881,8 @@ impl WalkTheDogStateMachine {
     keystate: &KeyState,
   ) -> Self {
     match self {
-      WalkTheDogStateMachine::GameOver(state) => state.update().into(),
+      WalkTheDogStateMachine::GameOver(state) => state.update(keystate).into(),
+      WalkTheDogStateMachine::Paused(state) => state.update(keystate).into(),
       WalkTheDogStateMachine::Ready(state) => state.update(keystate).into(),
       WalkTheDogStateMachine::Walking(state) => state.update(keystate).into(),
     }
@@ -625,11 +1906,124 @@ impl WalkTheDogState<GameOver> {
     }
   }
 
-  fn update(mut self) -> GameOverEndState {
-    if self._state.new_game_pressed() {
-      GameOverEndState::Complete(self.new_game())
-    } else {
-      GameOverEndState::Continue(self)
+  fn rebind(
+    &mut self,
+    action: Action,
+    code: &str,
+  ) {
+    match self.walk.settings.bindings.bind(action, code) {
+      Ok(()) => self.save_settings(),
+      Err(err) => {
+        log!("Could not rebind {} {:#?}", action.label(), err);
+      },
+    }
+    self.show_bindings();
+  }
+
+  fn reset_bindings(&mut self) {
+    self.walk.settings.bindings = Bindings::default();
+    self.save_settings();
+    self.show_bindings();
+  }
+
+  fn save_settings(&self) {
+    if let Err(err) = self.walk.settings.save() {
+      log!("Error saving settings {:#?}", err);
+    }
+  }
+
+  fn set_volume(
+    &mut self,
+    volume: f32,
+  ) {
+    self.walk.settings.volume = volume.clamp(0.0, 1.0);
+    self.walk.audio().set_volume(self.walk.settings.volume);
+    self.save_settings();
+    let _result: Result<()> = browser::set_inner_text(
+      "volume",
+      &volume_text(self.walk.settings.volume),
+    );
+  }
+
+  fn set_lives(
+    &mut self,
+    lives: u8,
+  ) {
+    self.walk.settings.lives = lives.clamp(1, MAX_LIVES);
+    self.save_settings();
+    let _result: Result<()> =
+      browser::set_inner_text("lives", &self.walk.settings.lives.to_string());
+  }
+
+  fn show_bindings(&self) {
+    Action::ALL.iter().for_each(|action| {
+      let _result: Result<()> = browser::set_inner_text(
+        &format!("binding_{}", action.id()),
+        &self.walk.settings.bindings.codes(*action).join(", "),
+      );
+    });
+  }
+
+  fn submit_score(&mut self) {
+    let Some(recording) = self._state.recording.clone() else {
+      return;
+    };
+    let name = browser::input_value("player_name").unwrap_or_default();
+    self.walk.settings.player_name = name.clone();
+    self.save_settings();
+    let submission = Submission {
+      name,
+      recording,
+      result: self.walk.result(),
+    };
+    let _result: Result<()> =
+      browser::set_inner_text("submit_score", "Submitted");
+    show_leaderboard(async move { leaderboard::submit(&submission).await });
+  }
+
+  fn update(
+    mut self,
+    keystate: &KeyState,
+  ) -> GameOverEndState {
+    if let Some(action) = self._state.rebind_pressed() {
+      self._state.rebinding = Some(action);
+      let _result: Result<()> = browser::set_inner_text(
+        &format!("binding_{}", action.id()),
+        "Press a key",
+      );
+    }
+    if let Some(action) = self._state.rebinding {
+      // Keys pressed together are ignored since their order is arbitrary
+      let mut codes = keystate.just_pressed_codes();
+      if let (Some(code), None) = (codes.next(), codes.next()) {
+        let code = code.clone();
+        self._state.rebinding = None;
+        self.rebind(action, &code);
+      }
+    }
+    if self._state.reset_bindings_pressed() {
+      self._state.rebinding = None;
+      self.reset_bindings();
+    }
+    if self._state.lives_down_pressed() {
+      self.set_lives(self.walk.settings.lives.saturating_sub(1));
+    }
+    if self._state.lives_up_pressed() {
+      self.set_lives(self.walk.settings.lives + 1);
+    }
+    if self._state.submit_score_pressed() {
+      self.submit_score();
+    }
+    if self._state.volume_down_pressed() {
+      self.set_volume(self.walk.settings.volume - VOLUME_STEP);
+    }
+    if self._state.volume_up_pressed() {
+      self.set_volume(self.walk.settings.volume + VOLUME_STEP);
+    }
+    if self._state.new_game_pressed() {
+      GameOverEndState::Complete(self.new_game())
+    } else {
+      GameOverEndState::Continue(self)
     }
   }
 }
@@ -649,9 +2043,97 @@ impl From<GameOverEndState> for WalkTheDogStateMachine {
 }
 
 impl GameOver {
+  fn lives_down_pressed(&mut self) -> bool {
+    matches!(self.lives_down_event.try_next(), Ok(Some(())))
+  }
+
+  fn lives_up_pressed(&mut self) -> bool {
+    matches!(self.lives_up_event.try_next(), Ok(Some(())))
+  }
+
   fn new_game_pressed(&mut self) -> bool {
     matches!(self.new_game_event.try_next(), Ok(Some(())))
   }
+
+  fn rebind_pressed(&mut self) -> Option<Action> {
+    self
+      .rebind_events
+      .iter_mut()
+      .find_map(|(action, receiver)| {
+        matches!(receiver.try_next(), Ok(Some(()))).then_some(*action)
+      })
+  }
+
+  fn reset_bindings_pressed(&mut self) -> bool {
+    matches!(self.reset_bindings_event.try_next(), Ok(Some(())))
+  }
+
+  fn submit_score_pressed(&mut self) -> bool {
+    let pressed = self
+      .submit_score_event
+      .as_mut()
+      .is_some_and(|receiver| matches!(receiver.try_next(), Ok(Some(()))));
+    if pressed {
+      self.submit_score_event = None;
+    }
+    pressed
+  }
+
+  fn volume_down_pressed(&mut self) -> bool {
+    matches!(self.volume_down_event.try_next(), Ok(Some(())))
+  }
+
+  fn volume_up_pressed(&mut self) -> bool {
+    matches!(self.volume_up_event.try_next(), Ok(Some(())))
+  }
+}
+
+impl Paused {
+  fn resume_pressed(&mut self) -> bool {
+    matches!(self.resume_event.try_next(), Ok(Some(())))
+  }
+}
+
+enum PausedEndState {
+  Continue(WalkTheDogState<Paused>),
+  Complete(WalkTheDogState<Walking>),
+}
+
+impl From<PausedEndState> for WalkTheDogStateMachine {
+  fn from(state: PausedEndState) -> Self {
+    match state {
+      PausedEndState::Continue(paused) => paused.into(),
+      PausedEndState::Complete(walking) => walking.into(),
+    }
+  }
+}
+
+impl WalkTheDogState<Paused> {
+  fn resume(self) -> WalkTheDogState<Walking> {
+    let _result: Result<()> = browser::hide_ui();
+    let walking = WalkTheDogState {
+      _state: Walking,
+      walk: self.walk,
+    };
+    walking.resume_audio()
+  }
+
+  fn update(
+    mut self,
+    keystate: &KeyState,
+  ) -> PausedEndState {
+    if self
+      .walk
+      .settings
+      .bindings
+      .just_pressed(keystate, Action::Pause)
+      || self._state.resume_pressed()
+    {
+      PausedEndState::Complete(self.resume())
+    } else {
+      PausedEndState::Continue(self)
+    }
+  }
 }
 
 enum ReadyEndState {
@@ -682,8 +2164,10 @@ impl WalkTheDogState<Ready> {
     mut self,
     keystate: &KeyState,
   ) -> ReadyEndState {
+    let keystate = self.walk.tick_input(keystate);
     self.walk.boy.update();
-    if keystate.is_pressed("ArrowRight") {
+    self.walk.update_dog();
+    if self.walk.bindings().is_pressed(&keystate, Action::Run) {
       ReadyEndState::Complete(self.start_running())
     } else {
       ReadyEndState::Continue(self)
@@ -694,6 +2178,7 @@ impl WalkTheDogState<Ready> {
 enum WalkingEndState {
   Continue(WalkTheDogState<Walking>),
   Complete(WalkTheDogState<GameOver>),
+  Paused(WalkTheDogState<Paused>),
 }
 
 impl From<WalkingEndState> for WalkTheDogStateMachine {
@@ -701,57 +2186,124 @@ impl From<WalkingEndState> for WalkTheDogStateMachine {
     match state {
       WalkingEndState::Continue(walking) => walking.into(),
       WalkingEndState::Complete(game_over) => game_over.into(),
+      WalkingEndState::Paused(paused) => paused.into(),
     }
   }
 }
 
 impl WalkTheDogState<Walking> {
   fn end_game(self) -> WalkTheDogState<GameOver> {
-    let receiver = browser::draw_ui("<button id='new_game'>New Game</button>")
-      .and_then(|_unit| browser::find_html_element_by_id("new_game"))
+    let mut stats = Stats::load();
+    if let InputSource::Live(_) = self.walk.input {
+      stats.record_run(self.walk.meters());
+      if let Err(err) = stats.save() {
+        log!("Error saving stats {:#?}", err);
+      }
+    }
+    let recording = self.walk.recording();
+    let submittable = recording
+      .as_ref()
+      .is_some_and(|recording| recording.lives <= DEFAULT_LIVES);
+    let replay_link = recording
+      .as_ref()
+      .and_then(|recording| replay_link(recording).ok())
+      .unwrap_or_default();
+    let receiver = browser::draw_ui(&format!(
+      "<div><button id='new_game'>New Game</button>\
+      <p>Score: {}</p><p>Distance: {}m</p>{}{}<p>Seed: {}</p>{}{}{}{}</div>",
+      self.walk.score(),
+      self.walk.meters(),
+      stats_html(&stats),
+      leaderboard_html(submittable, &self.walk.settings.player_name),
+      self.walk.seed,
+      replay_link,
+      lives_html(self.walk.settings.lives),
+      volume_html(self.walk.settings.volume),
+      bindings_html(&self.walk.settings.bindings)
+    ))
+    .and_then(|_unit| browser::find_html_element_by_id("new_game"))
+    .map(engine::add_click_handler)
+    .unwrap();
+    let rebind_events = Action::ALL
+      .iter()
+      .map(|action| {
+        let receiver =
+          browser::find_html_element_by_id(&format!("rebind_{}", action.id()))
+            .map(engine::add_click_handler)
+            .unwrap();
+        (*action, receiver)
+      })
+      .collect();
+    let reset_bindings_event =
+      browser::find_html_element_by_id("reset_bindings")
+        .map(engine::add_click_handler)
+        .unwrap();
+    let lives_down_event = browser::find_html_element_by_id("lives_down")
+      .map(engine::add_click_handler)
+      .unwrap();
+    let lives_up_event = browser::find_html_element_by_id("lives_up")
+      .map(engine::add_click_handler)
+      .unwrap();
+    let submit_score_event = submittable
+      .then(|| {
+        browser::find_html_element_by_id("submit_score")
+          .map(engine::add_click_handler)
+          .ok()
+      })
+      .flatten();
+    show_leaderboard(leaderboard::top_scores());
+    let volume_down_event = browser::find_html_element_by_id("volume_down")
+      .map(engine::add_click_handler)
+      .unwrap();
+    let volume_up_event = browser::find_html_element_by_id("volume_up")
       .map(engine::add_click_handler)
       .unwrap();
     WalkTheDogState {
       _state: GameOver {
+        lives_down_event,
+        lives_up_event,
         new_game_event: receiver,
+        rebind_events,
+        rebinding: None,
+        recording,
+        reset_bindings_event,
+        submit_score_event,
+        volume_down_event,
+        volume_up_event,
       },
       walk: self.walk,
     }
   }
 
+  fn pause(self) -> WalkTheDogState<Paused> {
+    let receiver = browser::draw_ui("<button id='resume'>Resume</button>")
+      .and_then(|_unit| browser::find_html_element_by_id("resume"))
+      .map(engine::add_click_handler)
+      .unwrap();
+    let paused = WalkTheDogState {
+      _state: Paused {
+        resume_event: receiver,
+      },
+      walk: self.walk,
+    };
+    paused.pause_audio()
+  }
+
   fn update(
     mut self,
     keystate: &KeyState,
   ) -> WalkingEndState {
-    if keystate.is_pressed("ArrowDown") {
-      log!("ArrowDown");
-      self.walk.boy.slide();
-    }
-    if keystate.is_pressed("Space") {
-      log!("Space");
-      self.walk.boy.jump();
-    }
-    self.walk.boy.update();
-    let walking_speed = self.walk.velocity();
-    let [first_background, second_background] = &mut self.walk.backgrounds;
-    first_background.move_horizontally(walking_speed);
-    second_background.move_horizontally(walking_speed);
-    if first_background.right() < 0 {
-      first_background.set_x(second_background.right());
-    }
-    if second_background.right() < 0 {
-      second_background.set_x(first_background.right());
-    }
-    self.walk.obstacles.retain(|obstacle| obstacle.right() > 0);
-    self.walk.obstacles.iter_mut().for_each(|obstacle| {
-      obstacle.move_horizontally(walking_speed);
-      obstacle.check_intersection(&mut self.walk.boy);
-    });
-    if self.walk.timeline < TIMELINE_MINIMUM {
-      self.walk.generate_next_segment();
-    } else {
-      self.walk.timeline += walking_speed;
+    if self
+      .walk
+      .settings
+      .bindings
+      .just_pressed(keystate, Action::Pause)
+    {
+      return WalkingEndState::Paused(self.pause());
     }
+    let keystate = self.walk.tick_input(keystate);
+    self.walk.update(&keystate);
+    self.walk.update_dog();
     if self.walk.knocked_out() {
       WalkingEndState::Complete(self.end_game())
     } else {
@@ -775,6 +2327,12 @@ impl From<WalkTheDogState<GameOver>> for WalkTheDogStateMachine {
   }
 }
 
+impl From<WalkTheDogState<Paused>> for WalkTheDogStateMachine {
+  fn from(state: WalkTheDogState<Paused>) -> Self {
+    WalkTheDogStateMachine::Paused(state)
+  }
+}
+
 impl From<WalkTheDogState<Ready>> for WalkTheDogStateMachine {
   fn from(state: WalkTheDogState<Ready>) -> Self {
     WalkTheDogStateMachine::Ready(state)
@@ -791,68 +2349,46 @@ impl From<WalkTheDogState<Walking>> for WalkTheDogStateMachine {
 impl Game for WalkTheDog {
   fn draw(
     &self,
-    renderer: &Renderer,
+    renderer: &dyn Renderer,
+    alpha: f32,
   ) {
     renderer.clear(&Rect {
       position: Point {
         x: 0,
         y: 0,
       },
-      width: 600,
-      height: 600,
+      width: WIDTH,
+      height: HEIGHT,
     });
     if let Some(machine) = &self.machine {
-      machine.draw(renderer);
+      machine.draw(renderer, alpha);
     }
   }
 
   async fn initialize(&self) -> Result<Box<dyn Game>> {
     match self.machine {
       None => {
-        let json: JsValue = browser::fetch_json("rhb.json").await?;
-        let sheet: Sheet = serde_wasm_bindgen::from_value(json).unwrap();
-        let background: HtmlImageElement = engine::load_image("BG.png").await?;
-        let stone: HtmlImageElement = engine::load_image("Stone.png").await?;
-        let tiles = browser::fetch_json("tiles.json").await?;
-        let sprite_sheet = Rc::new(SpriteSheet::new(
-          engine::load_image("tiles.png").await?,
-          serde_wasm_bindgen::from_value(tiles).unwrap(),
-        ));
-        let image: HtmlImageElement = engine::load_image("rhb.png").await?;
+        let assets = load_assets().await?;
+        browser::load_font(HUD_FONT).await?;
         let audio = Audio::new()?;
+        let settings = Settings::load();
+        audio.set_volume(settings.volume);
         let sound = audio.load_sound("SFX_Jump_23.mp3").await?;
+        let pickup_sound = audio.load_sound("SFX_Coin.wav").await?;
         let background_music = audio.load_sound("background_song.mp3").await?;
         audio.play_looping_sound(&background_music)?;
-        let rhb: RedHatBoy = RedHatBoy::new(audio, image, sound, sheet);
-        let background_width = background.width() as i16;
-        let backgrounds = [
-          Image::new(
-            background.clone(),
-            Point {
-              x: 0,
-              y: 0,
-            },
-          ),
-          Image::new(
-            background,
-            Point {
-              x: background_width,
-              y: 0,
-            },
-          ),
-        ];
-        // let sprite_sheet_clone: Rc<SpriteSheet> = sprite_sheet.clone();
-        let starting_obstacles =
-          stone_and_platform(0, sprite_sheet.clone(), stone.clone());
-        let timeline = rightmost(&starting_obstacles);
-        let machine = WalkTheDogStateMachine::new(Walk {
-          boy: rhb,
-          backgrounds,
-          obstacle_sheet: sprite_sheet,
-          obstacles: starting_obstacles,
-          stone,
-          timeline,
-        });
+        let (input, seed) = match load_recording().await? {
+          Some(recording) => {
+            let seed = recording.seed;
+            (InputSource::Replay(Replay::new(recording)), seed)
+          },
+          None => (InputSource::Live(InputRecorder::new()), choose_seed()),
+        };
+        log!("Seed: {}", seed);
+        let mut walk =
+          Walk::new(assets, audio, sound, pickup_sound, input, seed, settings);
+        walk.show_hitboxes = show_hitboxes();
+        let machine = WalkTheDogStateMachine::new(walk);
         Ok(Box::new(WalkTheDog {
           machine: Some(machine),
         }))
@@ -861,6 +2397,42 @@ impl Game for WalkTheDog {
     }
   }
 
+  fn virtual_buttons(&self) -> Vec<VirtualButton> {
+    let y = HEIGHT - VIRTUAL_BUTTON_SIZE;
+    vec![
+      VirtualButton {
+        code: "VirtualSlide".into(),
+        rect: Rect::new_from_x_y(
+          0,
+          y,
+          VIRTUAL_BUTTON_SIZE,
+          VIRTUAL_BUTTON_SIZE,
+        ),
+      },
+      VirtualButton {
+        code: "VirtualJump".into(),
+        rect: Rect::new_from_x_y(
+          WIDTH - VIRTUAL_BUTTON_SIZE,
+          y,
+          VIRTUAL_BUTTON_SIZE,
+          VIRTUAL_BUTTON_SIZE,
+        ),
+      },
+    ]
+  }
+
+  fn pause(&mut self) {
+    if let Some(machine) = self.machine.take() {
+      self.machine.replace(machine.pause());
+    }
+  }
+
+  fn resume(&mut self) {
+    if let Some(machine) = self.machine.take() {
+      self.machine.replace(machine.resume());
+    }
+  }
+
   fn update(
     &mut self,
     keystate: &KeyState,
@@ -872,32 +2444,156 @@ impl Game for WalkTheDog {
   }
 }
 
-mod red_hat_boy_states {
+pub mod red_hat_boy_states {
 
   use super::HEIGHT;
   use crate::engine::{Audio, Point, Sound};
 
+  const AIRBORNE_FIRST_FRAME: u8 = 18; // 'Jump' frames 7 to 12 in the sheet
+  pub const COYOTE_TICKS: u8 = 6;
   const FALLING_FRAME_NAME: &str = "Dead";
   const FALLING_FRAMES: u8 = 29; // 10 'Dead' frames in the sheet, * 3 - 1
-  const FLOOR: i16 = 479;
-  const GRAVITY: i16 = 1;
+  pub const FLOOR: i16 = 479;
+  pub const GRAVITY: i16 = 1;
+  const HURT_FRAME_NAME: &str = "Hurt";
+  const HURT_FRAMES: u8 = 23; // 8 'Hurt' frames in the sheet, * 3 - 1
   const IDLE_FRAME_NAME: &str = "Idle";
   const IDLE_FRAMES: u8 = 29;
+  const INVULNERABLE_FRAMES: u8 = 90;
+  const JUMP_BUFFER_TICKS: u8 = 8;
   const JUMP_FRAME_NAME: &str = "Jump";
-  const JUMP_SPEED: i16 = -25;
+  const JUMP_RELEASE_SPEED: i16 = -10;
+  pub const JUMP_SPEED: i16 = -25;
   const JUMPING_FRAMES: u8 = 35; // TODO: why is this 35?
-  const PLAYER_HEIGHT: i16 = HEIGHT - FLOOR;
+  const KNOCKBACK_JUMP_SPEED: i16 = -10;
+  const KNOCKBACK_SPEED: i16 = -3;
+  pub const PLAYER_HEIGHT: i16 = HEIGHT - FLOOR;
+  const POWER_UP_TICKS: u16 = 600;
+  const PUSH_BACK_MAX_DISTANCE: i16 = 100;
   const RUN_FRAME_NAME: &str = "Run";
   const RUNNING_FRAMES: u8 = 23;
-  const RUNNING_SPEED: i16 = 4;
+  pub const RUNNING_SPEED: i16 = 4;
   const SLIDING_FRAMES: u8 = 14;
+  const SLIDING_LOOP_FRAME: u8 = 6; // 'Slide' frames 3 to 5 repeat while held
   const SLIDING_FRAME_NAME: &str = "Slide";
   const STARTING_POINT: i16 = -20;
-  const TERMINAL_VELOCITY: i16 = 20;
+  const SUPPORT_DEPTH: i16 = 4; // feet sink up to 3 pixels on a grounded tick
+  pub const TERMINAL_VELOCITY: i16 = 20;
+
+  #[derive(Clone, Copy, Eq, Hash, PartialEq)]
+  pub struct Footing {
+    pub coyote_ticks: u8,
+    pub velocity_y: i16,
+    pub y: i16,
+  }
+
+  impl Footing {
+    pub fn can_jump(&self) -> bool {
+      self.coyote_ticks > 0
+    }
+
+    // Touching a platform after a one pixel fall does not overlap it yet
+    pub fn grounded(&self) -> bool {
+      self.coyote_ticks >= COYOTE_TICKS - 1
+    }
+
+    pub fn land(
+      mut self,
+      y: i16,
+    ) -> Self {
+      self.coyote_ticks = COYOTE_TICKS;
+      self.velocity_y = 0;
+      self.y = y;
+      self
+    }
+
+    pub fn step(mut self) -> Self {
+      if self.velocity_y < TERMINAL_VELOCITY {
+        self.velocity_y += GRAVITY;
+      }
+      self.coyote_ticks = self.coyote_ticks.saturating_sub(1);
+      self.y += self.velocity_y;
+      if self.y >= FLOOR {
+        self.land(FLOOR)
+      } else {
+        self
+      }
+    }
+  }
+
+  #[derive(Clone, Copy)]
+  pub enum PowerUp {
+    DoubleJump,
+    Magnet,
+    Shield,
+  }
+
+  impl PowerUp {
+    pub const ALL: [PowerUp; 3] = [
+      PowerUp::Shield,
+      PowerUp::DoubleJump,
+      PowerUp::Magnet,
+    ];
+
+    pub fn label(&self) -> &str {
+      match self {
+        PowerUp::DoubleJump => "Double Jump",
+        PowerUp::Magnet => "Magnet",
+        PowerUp::Shield => "Shield",
+      }
+    }
+  }
+
+  #[derive(Clone, Copy, Default)]
+  pub struct PowerUps {
+    double_jump: u16,
+    magnet: u16,
+    shield: u16,
+  }
+
+  impl PowerUps {
+    pub fn remaining(
+      &self,
+      power_up: PowerUp,
+    ) -> u16 {
+      match power_up {
+        PowerUp::DoubleJump => self.double_jump,
+        PowerUp::Magnet => self.magnet,
+        PowerUp::Shield => self.shield,
+      }
+    }
+
+    fn set(
+      mut self,
+      power_up: PowerUp,
+      ticks: u16,
+    ) -> Self {
+      match power_up {
+        PowerUp::DoubleJump => self.double_jump = ticks,
+        PowerUp::Magnet => self.magnet = ticks,
+        PowerUp::Shield => self.shield = ticks,
+      }
+      self
+    }
+
+    fn tick(self) -> Self {
+      PowerUps {
+        double_jump: self.double_jump.saturating_sub(1),
+        magnet: self.magnet.saturating_sub(1),
+        shield: self.shield.saturating_sub(1),
+      }
+    }
+  }
+
+  #[derive(Clone, Copy)]
+  pub struct Airborne;
 
   #[derive(Clone, Copy)]
   pub struct Falling;
 
+  #[derive(Clone, Copy)]
+  pub struct Hurt;
+
   #[derive(Clone, Copy)]
   pub struct Idle;
 
@@ -922,13 +2618,65 @@ mod red_hat_boy_states {
   #[derive(Clone)]
   pub struct RedHatBoyContext {
     pub audio: Audio,
+    pub coyote_ticks: u8,
+    pub double_jumped: bool,
     pub frame: u8,
+    pub invulnerable: u8,
+    pub jump_buffer_ticks: u8,
     pub jump_sound: Sound,
+    pub lives: u8,
     pub position: Point,
+    pub power_ups: PowerUps,
     pub velocity: Point,
   }
 
   impl RedHatBoyContext {
+    pub fn absorb_hit(mut self) -> Self {
+      self.invulnerable = INVULNERABLE_FRAMES;
+      self.power_ups = self.power_ups.set(PowerUp::Shield, 0);
+      self
+    }
+
+    pub fn bump(
+      mut self,
+      depth: i16,
+    ) -> Self {
+      self.position.y += depth;
+      self.velocity.y = self.velocity.y.max(0);
+      self
+    }
+
+    pub fn can_double_jump(&self) -> bool {
+      !self.double_jumped && self.power_ups.remaining(PowerUp::DoubleJump) > 0
+    }
+
+    pub fn can_push_back(
+      &self,
+      x: i16,
+    ) -> bool {
+      self.position.x + x >= STARTING_POINT - PUSH_BACK_MAX_DISTANCE
+    }
+
+    pub fn footing(&self) -> Footing {
+      Footing {
+        coyote_ticks: self.coyote_ticks,
+        velocity_y: self.velocity.y,
+        y: self.position.y,
+      }
+    }
+
+    fn knock_back(mut self) -> Self {
+      self.invulnerable = INVULNERABLE_FRAMES;
+      self.velocity.x = KNOCKBACK_SPEED;
+      self.velocity.y = KNOCKBACK_JUMP_SPEED;
+      self
+    }
+
+    fn lose_life(mut self) -> Self {
+      self.lives = self.lives.saturating_sub(1);
+      self
+    }
+
     fn play_jump_sound(self) -> Self {
       if let Err(err) = self.audio.play_sound(&self.jump_sound) {
         log!("Error playing jump sound {:#?}", err);
@@ -936,6 +2684,22 @@ mod red_hat_boy_states {
       self
     }
 
+    pub fn power_up(
+      mut self,
+      power_up: PowerUp,
+    ) -> Self {
+      self.power_ups = self.power_ups.set(power_up, POWER_UP_TICKS);
+      self
+    }
+
+    pub fn push_back(
+      mut self,
+      x: i16,
+    ) -> Self {
+      self.position.x += x;
+      self
+    }
+
     fn reset_frame(mut self) -> Self {
       self.frame = 0;
       self
@@ -946,15 +2710,40 @@ mod red_hat_boy_states {
       self
     }
 
-    fn set_on(
+    fn set_double_jumped(
       mut self,
-      position: i16,
+      double_jumped: bool,
+    ) -> Self {
+      self.double_jumped = double_jumped;
+      self
+    }
+
+    fn set_jump_buffer_ticks(
+      mut self,
+      jump_buffer_ticks: u8,
+    ) -> Self {
+      self.jump_buffer_ticks = jump_buffer_ticks;
+      self
+    }
+
+    fn set_footing(
+      mut self,
+      footing: Footing,
     ) -> Self {
-      let position = position - PLAYER_HEIGHT;
-      self.position.y = position;
+      self.coyote_ticks = footing.coyote_ticks;
+      self.position.y = footing.y;
+      self.velocity.y = footing.velocity_y;
       self
     }
 
+    fn set_on(
+      self,
+      position: i16,
+    ) -> Self {
+      let footing = self.footing().land(position - PLAYER_HEIGHT);
+      self.set_footing(footing)
+    }
+
     fn set_vertical_velocity(
       mut self,
       y: i16,
@@ -963,6 +2752,18 @@ mod red_hat_boy_states {
       self
     }
 
+    pub fn standing_on(
+      &self,
+      top: i16,
+    ) -> bool {
+      let sink = self.position.y + PLAYER_HEIGHT - top;
+      self.footing().grounded() && (0..=SUPPORT_DEPTH).contains(&sink)
+    }
+
+    fn supported(&self) -> bool {
+      self.footing().grounded()
+    }
+
     fn stop(mut self) -> Self {
       self.velocity.x = 0;
       self
@@ -972,19 +2773,19 @@ mod red_hat_boy_states {
       mut self,
       frame_count: u8,
     ) -> Self {
-      if self.velocity.y < TERMINAL_VELOCITY {
-        self.velocity.y += GRAVITY;
-      }
+      self.invulnerable = self.invulnerable.saturating_sub(1);
+      self.jump_buffer_ticks = self.jump_buffer_ticks.saturating_sub(1);
+      self.power_ups = self.power_ups.tick();
       if self.frame < frame_count {
         self.frame += 1;
       } else {
         self.frame = 0;
       }
-      self.position.y += self.velocity.y;
-      if self.position.y > FLOOR {
-        self.position.y = FLOOR;
+      if self.position.x < STARTING_POINT {
+        self.position.x += 1;
       }
-      self
+      let footing = self.footing().step();
+      self.set_footing(footing)
     }
   }
 
@@ -992,6 +2793,54 @@ mod red_hat_boy_states {
     pub fn context(&self) -> &RedHatBoyContext {
       &self.context
     }
+
+    pub fn map_context(
+      mut self,
+      f: impl FnOnce(RedHatBoyContext) -> RedHatBoyContext,
+    ) -> Self {
+      self.context = f(self.context);
+      self
+    }
+  }
+
+  impl RedHatBoyState<Airborne> {
+    pub fn buffer_jump(self) -> RedHatBoyState<Airborne> {
+      RedHatBoyState {
+        context: self.context.set_jump_buffer_ticks(JUMP_BUFFER_TICKS),
+        _state: Airborne,
+      }
+    }
+
+    pub fn frame_name(&self) -> &str {
+      JUMP_FRAME_NAME
+    }
+
+    pub fn jump(self) -> RedHatBoyState<Jumping> {
+      jump(self.context)
+    }
+
+    pub fn knock_out(self) -> KnockOutEndState {
+      knock_out(self.context)
+    }
+
+    pub fn land_on(
+      self,
+      position: i16,
+    ) -> LandingEndState {
+      land_on(self.context, position)
+    }
+
+    pub fn update(mut self) -> AirborneEndState {
+      self.context = self.context.update(JUMPING_FRAMES);
+      if self.context.frame < AIRBORNE_FIRST_FRAME {
+        self.context.frame = AIRBORNE_FIRST_FRAME;
+      }
+      if self.context.position.y >= FLOOR {
+        AirborneEndState::Landing(self.land_on(HEIGHT))
+      } else {
+        AirborneEndState::Airborne(self)
+      }
+    }
   }
 
   impl RedHatBoyState<Falling> {
@@ -1016,6 +2865,38 @@ mod red_hat_boy_states {
     }
   }
 
+  impl RedHatBoyState<Hurt> {
+    pub fn frame_name(&self) -> &str {
+      HURT_FRAME_NAME
+    }
+
+    pub fn land_on(
+      self,
+      position: i16,
+    ) -> RedHatBoyState<Hurt> {
+      RedHatBoyState {
+        context: self.context.set_on(position),
+        _state: Hurt,
+      }
+    }
+
+    pub fn recover(self) -> RedHatBoyState<Running> {
+      RedHatBoyState {
+        context: self.context.reset_frame().stop().run_right(),
+        _state: Running,
+      }
+    }
+
+    pub fn update(mut self) -> HurtEndState {
+      self.context = self.context.update(HURT_FRAMES);
+      if self.context.frame >= HURT_FRAMES {
+        HurtEndState::Complete(self.recover())
+      } else {
+        HurtEndState::Hurt(self)
+      }
+    }
+  }
+
   impl RedHatBoyState<Idle> {
     pub fn frame_name(&self) -> &str {
       IDLE_FRAME_NAME
@@ -1024,16 +2905,23 @@ mod red_hat_boy_states {
     pub fn new(
       audio: Audio,
       jump_sound: Sound,
+      lives: u8,
     ) -> Self {
       RedHatBoyState {
         context: RedHatBoyContext {
           audio,
+          coyote_ticks: 0,
+          double_jumped: false,
           frame: 0,
+          invulnerable: 0,
+          jump_buffer_ticks: 0,
           jump_sound,
+          lives,
           position: Point {
             x: STARTING_POINT,
             y: FLOOR,
           },
+          power_ups: PowerUps::default(),
           velocity: Point {
             x: 0,
             y: 0,
@@ -1057,26 +2945,46 @@ mod red_hat_boy_states {
   }
 
   impl RedHatBoyState<Jumping> {
-    pub fn frame_name(&self) -> &str {
-      JUMP_FRAME_NAME
+    pub fn buffer_jump(self) -> RedHatBoyState<Jumping> {
+      RedHatBoyState {
+        context: self.context.set_jump_buffer_ticks(JUMP_BUFFER_TICKS),
+        _state: Jumping,
+      }
     }
 
-    pub fn knock_out(self) -> RedHatBoyState<Falling> {
+    pub fn double_jump(self) -> RedHatBoyState<Jumping> {
       RedHatBoyState {
-        context: self.context.reset_frame().stop(),
-        _state: Falling,
+        context: self
+          .context
+          .reset_frame()
+          .set_vertical_velocity(JUMP_SPEED)
+          .set_double_jumped(true)
+          .play_jump_sound(),
+        _state: Jumping,
       }
     }
 
+    pub fn frame_name(&self) -> &str {
+      JUMP_FRAME_NAME
+    }
+
+    pub fn knock_out(self) -> KnockOutEndState {
+      knock_out(self.context)
+    }
+
     pub fn land_on(
       self,
       position: i16,
-    ) -> RedHatBoyState<Running> {
+    ) -> LandingEndState {
       log!("land_on");
-      RedHatBoyState {
-        context: self.context.reset_frame().set_on(position),
-        _state: Running,
+      land_on(self.context, position)
+    }
+
+    pub fn release_jump(mut self) -> RedHatBoyState<Jumping> {
+      if self.context.velocity.y < JUMP_RELEASE_SPEED {
+        self.context = self.context.set_vertical_velocity(JUMP_RELEASE_SPEED);
       }
+      self
     }
 
     pub fn update(mut self) -> JumpingEndState {
@@ -1106,22 +3014,21 @@ mod red_hat_boy_states {
       RUN_FRAME_NAME
     }
 
-    pub fn jump(self) -> RedHatBoyState<Jumping> {
+    pub fn fall(self) -> RedHatBoyState<Airborne> {
+      let mut context = self.context;
+      context.frame = AIRBORNE_FIRST_FRAME;
       RedHatBoyState {
-        context: self
-          .context
-          .reset_frame()
-          .set_vertical_velocity(JUMP_SPEED)
-          .play_jump_sound(),
-        _state: Jumping {},
+        context,
+        _state: Airborne,
       }
     }
 
-    pub fn knock_out(self) -> RedHatBoyState<Falling> {
-      RedHatBoyState {
-        context: self.context.reset_frame().stop(),
-        _state: Falling {},
-      }
+    pub fn jump(self) -> RedHatBoyState<Jumping> {
+      jump(self.context)
+    }
+
+    pub fn knock_out(self) -> KnockOutEndState {
+      knock_out(self.context)
     }
 
     pub fn land_on(
@@ -1141,9 +3048,14 @@ mod red_hat_boy_states {
       }
     }
 
-    pub fn update(mut self) -> Self {
+    pub fn update(mut self) -> RunningEndState {
+      let supported = self.context.supported();
       self.context = self.context.update(RUNNING_FRAMES);
-      self
+      if supported {
+        RunningEndState::Running(self)
+      } else {
+        RunningEndState::Airborne(self.fall())
+      }
     }
   }
 
@@ -1152,11 +3064,8 @@ mod red_hat_boy_states {
       SLIDING_FRAME_NAME
     }
 
-    pub fn knock_out(self) -> RedHatBoyState<Falling> {
-      RedHatBoyState {
-        context: self.context.reset_frame().stop(),
-        _state: Falling,
-      }
+    pub fn knock_out(self) -> KnockOutEndState {
+      knock_out(self.context)
     }
 
     pub fn land_on(
@@ -1170,6 +3079,13 @@ mod red_hat_boy_states {
       }
     }
 
+    pub fn slide(mut self) -> RedHatBoyState<Sliding> {
+      if self.context.frame >= SLIDING_FRAMES - 1 {
+        self.context.frame = SLIDING_LOOP_FRAME;
+      }
+      self
+    }
+
     pub fn stand(self) -> RedHatBoyState<Running> {
       RedHatBoyState {
         context: self.context.reset_frame(),
@@ -1188,14 +3104,81 @@ mod red_hat_boy_states {
     }
   }
 
+  fn jump(context: RedHatBoyContext) -> RedHatBoyState<Jumping> {
+    RedHatBoyState {
+      context: context
+        .reset_frame()
+        .set_vertical_velocity(JUMP_SPEED)
+        .set_double_jumped(false)
+        .set_jump_buffer_ticks(0)
+        .play_jump_sound(),
+      _state: Jumping,
+    }
+  }
+
+  fn knock_out(context: RedHatBoyContext) -> KnockOutEndState {
+    let context = context.reset_frame().lose_life();
+    if context.lives > 0 {
+      KnockOutEndState::Hurt(RedHatBoyState {
+        context: context.knock_back(),
+        _state: Hurt,
+      })
+    } else {
+      KnockOutEndState::Falling(RedHatBoyState {
+        context: context.stop(),
+        _state: Falling,
+      })
+    }
+  }
+
+  fn land_on(
+    context: RedHatBoyContext,
+    position: i16,
+  ) -> LandingEndState {
+    let context = context.reset_frame().set_on(position);
+    if context.jump_buffer_ticks > 0 {
+      LandingEndState::Jumping(jump(context))
+    } else {
+      LandingEndState::Running(RedHatBoyState {
+        context,
+        _state: Running,
+      })
+    }
+  }
+
+  pub enum AirborneEndState {
+    Airborne(RedHatBoyState<Airborne>),
+    Landing(LandingEndState),
+  }
+
   pub enum FallingEndState {
     Complete(RedHatBoyState<KnockedOut>),
     Falling(RedHatBoyState<Falling>),
   }
 
+  pub enum HurtEndState {
+    Complete(RedHatBoyState<Running>),
+    Hurt(RedHatBoyState<Hurt>),
+  }
+
   pub enum JumpingEndState {
     Jumping(RedHatBoyState<Jumping>),
-    Landing(RedHatBoyState<Running>),
+    Landing(LandingEndState),
+  }
+
+  pub enum KnockOutEndState {
+    Falling(RedHatBoyState<Falling>),
+    Hurt(RedHatBoyState<Hurt>),
+  }
+
+  pub enum LandingEndState {
+    Jumping(RedHatBoyState<Jumping>),
+    Running(RedHatBoyState<Running>),
+  }
+
+  pub enum RunningEndState {
+    Airborne(RedHatBoyState<Airborne>),
+    Running(RedHatBoyState<Running>),
   }
 
   pub enum SlidingEndState {
@@ -1204,6 +3187,230 @@ mod red_hat_boy_states {
   }
 }
 
+fn bindings_html(bindings: &Bindings) -> String {
+  let rows: String = Action::ALL
+    .iter()
+    .map(|action| {
+      format!(
+        "<li>{}: <span id='binding_{}'>{}</span> \
+        <span class='rebind' id='rebind_{}'>Add Key</span></li>",
+        action.label(),
+        action.id(),
+        bindings.codes(*action).join(", "),
+        action.id()
+      )
+    })
+    .collect();
+  format!(
+    "<ul class='bindings'>{}</ul>\
+    <p><span class='rebind' id='reset_bindings'>Reset Keys</span></p>",
+    rows
+  )
+}
+
+fn escape_html(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('\'', "&#39;")
+    .replace('"', "&quot;")
+}
+
+fn leaderboard_html(
+  submittable: bool,
+  player_name: &str,
+) -> String {
+  let submit = if submittable {
+    format!(
+      "<p><input id='player_name' maxlength='16' value='{}'> \
+      <span class='rebind' id='submit_score'>Submit Score</span></p>",
+      escape_html(player_name)
+    )
+  } else {
+    String::new()
+  };
+  format!(
+    "<p>Leaderboard</p><ol class='best' id='leaderboard'><li>Loading</li></ol>{}",
+    submit
+  )
+}
+
+fn lives_html(lives: u8) -> String {
+  format!(
+    "<p><span class='rebind' id='lives_down'>-</span> \
+    Lives: <span id='lives'>{}</span> \
+    <span class='rebind' id='lives_up'>+</span></p>",
+    lives
+  )
+}
+
+async fn load_assets() -> Result<Assets> {
+  let dog_json: JsValue = browser::fetch_json("dog.json").await?;
+  let rhb_json: JsValue = browser::fetch_json("rhb.json").await?;
+  let rhb_hitboxes_json: JsValue =
+    browser::fetch_json("rhb_hitboxes.json").await?;
+  let segments_json: JsValue = browser::fetch_json("segments.json").await?;
+  let pickups_json: JsValue = browser::fetch_json("pickups.json").await?;
+  let tiles_json: JsValue = browser::fetch_json("tiles.json").await?;
+  let assets = Assets {
+    background: engine::load_image("BG.png").await?,
+    dog_image: engine::load_image("dog.png").await?,
+    dog_sheet: serde_wasm_bindgen::from_value(dog_json).unwrap(),
+    pickups: Rc::new(SpriteSheet::new(
+      engine::load_image("pickups.png").await?,
+      serde_wasm_bindgen::from_value(pickups_json).unwrap(),
+    )),
+    rhb_image: engine::load_image("rhb.png").await?,
+    rhb_sheet: serde_wasm_bindgen::from_value::<Sheet>(rhb_json)
+      .unwrap()
+      .with_hitboxes(
+        serde_wasm_bindgen::from_value(rhb_hitboxes_json)
+          .map_err(|err| anyhow!("Could not parse hitboxes {:#?}", err))?,
+      ),
+    segments: serde_wasm_bindgen::from_value(segments_json)
+      .map_err(|err| anyhow!("Could not parse segments {:#?}", err))?,
+    stone: engine::load_image("Stone.png").await?,
+    tiles: Rc::new(SpriteSheet::new(
+      engine::load_image("tiles.png").await?,
+      serde_wasm_bindgen::from_value(tiles_json).unwrap(),
+    )),
+  };
+  assets.validate()?;
+  Ok(assets)
+}
+
+// Random seeds are narrowed to u32 because replays serialize the seed as a
+// JavaScript number, which cannot hold every u64 exactly
+fn choose_seed() -> u64 {
+  browser::query_parameter("seed")
+    .ok()
+    .flatten()
+    .and_then(|seed| seed.parse().ok())
+    .unwrap_or_else(|| thread_rng().gen::<u32>().into())
+}
+
+async fn load_recording() -> Result<Option<Recording>> {
+  match browser::query_parameter("replay").ok().flatten() {
+    Some(replay_path) => {
+      let json: JsValue = browser::fetch_json(&replay_path).await?;
+      serde_wasm_bindgen::from_value(json)
+        .map(Some)
+        .map_err(|err| anyhow!("Could not parse replay {:#?}", err))
+    },
+    None => Ok(None),
+  }
+}
+
+fn show_hitboxes() -> bool {
+  browser::query_parameter("hitboxes")
+    .ok()
+    .flatten()
+    .is_some()
+}
+
+// Replays recorded before lives were added ended on the first hit
+fn recording_lives() -> u8 {
+  1
+}
+
+fn replay_link(recording: &Recording) -> Result<String> {
+  let value = recording
+    .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+    .map_err(|err| anyhow!("Could not serialize replay {:#?}", err))?;
+  let json = browser::stringify_json(&value)?;
+  Ok(format!(
+    "<p><a download='replay.json' href='data:application/json,{}'>\
+    Save Replay</a></p>",
+    browser::encode_uri_component(&json)
+  ))
+}
+
+fn scores_html(scores: &[Score]) -> String {
+  scores
+    .iter()
+    .map(|score| {
+      format!(
+        "<li>{} {} ({}m)</li>",
+        escape_html(&score.name),
+        score.score,
+        score.distance
+      )
+    })
+    .collect()
+}
+
+fn show_leaderboard(
+  scores: impl Future<Output = Result<Vec<Score>>> + 'static
+) {
+  browser::spawn_local(async move {
+    let html = match scores.await {
+      Ok(scores) => scores_html(&scores),
+      Err(err) => match err.downcast_ref::<HttpStatus>() {
+        Some(HttpStatus(leaderboard::DUPLICATE_STATUS)) => {
+          "<li>Run already submitted</li>".into()
+        },
+        Some(HttpStatus(leaderboard::REJECTED_STATUS)) => {
+          "<li>Run rejected by the leaderboard</li>".into()
+        },
+        _ => {
+          log!("Error loading leaderboard {:#?}", err);
+          "<li>Leaderboard unavailable</li>".into()
+        },
+      },
+    };
+    let _result: Result<()> = browser::set_inner_html("leaderboard", &html);
+  });
+}
+
+pub fn simulate(
+  assets: Assets,
+  recording: Recording,
+  max_ticks: u32,
+) -> RunResult {
+  let seed = recording.seed;
+  let mut walk = Walk::new(
+    assets,
+    Audio::headless(),
+    Sound::headless(),
+    Sound::headless(),
+    InputSource::Replay(Replay::new(recording)),
+    seed,
+    Settings::default(),
+  );
+  let live_keystate = KeyState::default();
+  let mut running = false;
+  for _tick in 0..max_ticks {
+    let keystate = walk.tick_input(&live_keystate);
+    if running {
+      walk.update(&keystate);
+      if walk.knocked_out() {
+        break;
+      }
+    } else {
+      walk.boy.update();
+      if walk.bindings().is_pressed(&keystate, Action::Run) {
+        walk.boy.run_right();
+        running = true;
+      }
+    }
+  }
+  walk.result()
+}
+
+fn stats_html(stats: &Stats) -> String {
+  let rows: String = stats
+    .best_distances()
+    .iter()
+    .map(|distance| format!("<li>{}m</li>", distance))
+    .collect();
+  format!(
+    "<p>Runs: {}</p><p>Best Distances</p><ol class='best'>{}</ol>",
+    stats.total_runs(),
+    rows
+  )
+}
+
 fn rightmost(obstacle_list: &[Box<dyn Obstacle>]) -> i16 {
   obstacle_list
     .iter()
@@ -1211,3 +3418,243 @@ fn rightmost(obstacle_list: &[Box<dyn Obstacle>]) -> i16 {
     .max_by(|x, y| x.cmp(y))
     .unwrap_or(0)
 }
+
+fn volume_html(volume: f32) -> String {
+  format!(
+    "<p><span class='rebind' id='volume_down'>-</span> \
+    Volume: <span id='volume'>{}</span> \
+    <span class='rebind' id='volume_up'>+</span></p>",
+    volume_text(volume)
+  )
+}
+
+fn volume_text(volume: f32) -> String {
+  format!("{}%", (volume * 100.0).round())
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::engine::{DrawCall, RecordingRenderer};
+  use serde::de::DeserializeOwned;
+  use std::fs;
+  use std::path::{Path, PathBuf};
+
+  const MAX_TICKS: u32 = 10_000;
+
+  pub fn assets() -> Assets {
+    let assets = Assets {
+      background: image("BG.png"),
+      dog_image: image("dog.png"),
+      dog_sheet: json("dog.json"),
+      pickups: Rc::new(SpriteSheet::new(
+        image("pickups.png"),
+        json("pickups.json"),
+      )),
+      rhb_image: image("rhb.png"),
+      rhb_sheet: json::<Sheet>("rhb.json")
+        .with_hitboxes(json("rhb_hitboxes.json")),
+      segments: json("segments.json"),
+      stone: image("Stone.png"),
+      tiles: Rc::new(SpriteSheet::new(image("tiles.png"), json("tiles.json"))),
+    };
+    assets.validate().expect("Invalid assets");
+    assets
+  }
+
+  // Reads the size from the IHDR chunk of the PNG header
+  fn image(name: &str) -> ImageElement {
+    let png = fs::read(static_path(name)).expect("Could not read image");
+    let size = |offset: usize| {
+      i16::from_be_bytes([
+        png[offset + 2],
+        png[offset + 3],
+      ])
+    };
+    ImageElement::headless(name, size(16), size(20))
+  }
+
+  fn image_ids(draw_calls: &[DrawCall]) -> Vec<&str> {
+    draw_calls
+      .iter()
+      .filter_map(|draw_call| match draw_call {
+        DrawCall::Image {
+          image_id,
+          ..
+        } => Some(image_id.as_str()),
+        _ => None,
+      })
+      .collect()
+  }
+
+  fn input_replay(entries: &str) -> InputReplay {
+    let log = format!("{{\"e\":[{}]}}", entries);
+    InputReplay::new(serde_json::from_str(&log).expect("Could not parse log"))
+  }
+
+  fn json<T: DeserializeOwned>(name: &str) -> T {
+    let json = fs::read_to_string(static_path(name)).expect("Could not read");
+    serde_json::from_str(&json).expect("Could not parse")
+  }
+
+  fn static_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+      .join("static")
+      .join(name)
+  }
+
+  fn walk() -> Walk {
+    Walk::new(
+      assets(),
+      Audio::headless(),
+      Sound::headless(),
+      Sound::headless(),
+      InputSource::Live(InputRecorder::new()),
+      42,
+      Settings::default(),
+    )
+  }
+
+  #[test]
+  fn draw_ready_clears_then_draws_the_walk() {
+    let game = WalkTheDog {
+      machine: Some(WalkTheDogStateMachine::new(walk())),
+    };
+    let renderer = RecordingRenderer::new();
+    game.draw(&renderer, 1.0);
+    let draw_calls = renderer.take_draw_calls();
+    assert_eq!(
+      draw_calls.first(),
+      Some(&DrawCall::Clear(Rect::new_from_x_y(0, 0, WIDTH, HEIGHT)))
+    );
+    assert_eq!(
+      image_ids(&draw_calls)[..4],
+      ["BG.png", "BG.png", "dog.png", "rhb.png"]
+    );
+    assert!(image_ids(&draw_calls).contains(&"Stone.png"));
+    assert!(!draw_calls
+      .iter()
+      .any(|draw_call| matches!(draw_call, DrawCall::FillText { .. })));
+  }
+
+  #[test]
+  fn draw_walking_adds_the_hud() {
+    let game = WalkTheDog {
+      machine: Some(WalkTheDogStateMachine::Walking(WalkTheDogState {
+        _state: Walking,
+        walk: walk(),
+      })),
+    };
+    let renderer = RecordingRenderer::new();
+    game.draw(&renderer, 1.0);
+    let texts: Vec<String> = renderer
+      .draw_calls()
+      .iter()
+      .filter_map(|draw_call| match draw_call {
+        DrawCall::FillText {
+          text,
+          ..
+        } => Some(text.clone()),
+        _ => None,
+      })
+      .collect();
+    assert_eq!(
+      texts,
+      [
+        "Score: 0",
+        "Distance: 0m",
+        "Lives: 3",
+        "Pickups: 0"
+      ]
+    );
+  }
+
+  #[test]
+  fn platform_and_beam_lay_out_the_same_tiles_along_their_axis() {
+    let tiles = assets().tiles;
+    let bounding_boxes = [Rect::new_from_x_y(0, 0, 10, 10)];
+    let position = Point {
+      x: 300,
+      y: 100,
+    };
+    let sprites = [
+      "13.png", "14.png", "15.png",
+    ];
+    let platform =
+      Platform::new(&bounding_boxes, position, false, tiles.clone(), &sprites);
+    let beam = Beam::new(&bounding_boxes, position, tiles, &sprites);
+    assert_eq!(platform.right(), beam.right());
+    let renderer = RecordingRenderer::new();
+    platform.draw(&renderer, 1.0);
+    let platform_calls = renderer.take_draw_calls();
+    beam.draw(&renderer, 1.0);
+    let beam_calls = renderer.take_draw_calls();
+    assert_eq!(platform_calls.len(), sprites.len());
+    assert_eq!(beam_calls.len(), sprites.len());
+    let mut offset = Point::default();
+    platform_calls
+      .iter()
+      .zip(&beam_calls)
+      .for_each(|calls| match calls {
+        (
+          DrawCall::Image {
+            destination: platform_destination,
+            source: platform_source,
+            ..
+          },
+          DrawCall::Image {
+            destination: beam_destination,
+            source: beam_source,
+            ..
+          },
+        ) => {
+          assert_eq!(platform_source, beam_source);
+          assert_eq!(platform_destination.x(), position.x + offset.x);
+          assert_eq!(platform_destination.y(), position.y);
+          assert_eq!(beam_destination.x(), position.x);
+          assert_eq!(beam_destination.y(), position.y + offset.y);
+          offset.x += platform_source.width;
+          offset.y += platform_source.height;
+        },
+        _ => panic!("Expected only images"),
+      });
+  }
+
+  #[test]
+  fn replay_matches_live_play_across_pauses() {
+    // Space is held through the first pause and pressed during the second
+    let mut keyboard = input_replay(
+      r#"{"t":0,"c":"ArrowRight","p":true},
+      {"t":10,"c":"Space","p":true},{"t":40,"c":"Space","p":false},
+      {"t":80,"c":"Space","p":true},{"t":110,"c":"Space","p":false}"#,
+    );
+    let paused =
+      |tick: u32| (20..30).contains(&tick) || (75..85).contains(&tick);
+    let mut walk = walk();
+    let mut running = false;
+    let mut ticks = 0;
+    for tick in 0..MAX_TICKS {
+      let keystate = keyboard.next_tick().clone();
+      if paused(tick) {
+        continue;
+      }
+      ticks += 1;
+      let keystate = walk.tick_input(&keystate);
+      if running {
+        walk.update(&keystate);
+        if walk.knocked_out() {
+          break;
+        }
+      } else {
+        walk.boy.update();
+        if walk.bindings().is_pressed(&keystate, Action::Run) {
+          walk.boy.run_right();
+          running = true;
+        }
+      }
+    }
+    assert!(walk.knocked_out());
+    let recording = walk.recording().expect("Live runs are recorded");
+    assert_eq!(simulate(assets(), recording, ticks), walk.result());
+  }
+}
@@ -0,0 +1,47 @@
+use crate::browser;
+pub use crate::game::{simulate, Assets, Recording, RunResult};
+pub use crate::settings::DEFAULT_LIVES;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_URL: &str = "http://127.0.0.1:8081/scores";
+pub const DUPLICATE_STATUS: u16 = 409;
+pub const REJECTED_STATUS: u16 = 422;
+pub const TOP_SCORES: usize = 10;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Score {
+  pub distance: u32,
+  pub name: String,
+  pub score: u32,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Submission {
+  pub name: String,
+  pub recording: Recording,
+  pub result: RunResult,
+}
+
+fn leaderboard_url() -> String {
+  browser::query_parameter("leaderboard")
+    .ok()
+    .flatten()
+    .unwrap_or_else(|| DEFAULT_URL.into())
+}
+
+pub async fn submit(submission: &Submission) -> Result<Vec<Score>> {
+  let body = submission
+    .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+    .map_err(|err| anyhow!("Could not serialize submission {:#?}", err))?;
+  let json =
+    browser::fetch_json_with_body(&leaderboard_url(), "POST", &body).await?;
+  serde_wasm_bindgen::from_value(json)
+    .map_err(|err| anyhow!("Could not parse scores {:#?}", err))
+}
+
+pub async fn top_scores() -> Result<Vec<Score>> {
+  let json = browser::fetch_json(&leaderboard_url()).await?;
+  serde_wasm_bindgen::from_value(json)
+    .map_err(|err| anyhow!("Could not parse scores {:#?}", err))
+}
@@ -4,10 +4,15 @@ use wasm_bindgen::prelude::*;
 
 #[macro_use]
 mod browser;
-mod engine;
+mod controls;
+mod dog;
+pub mod engine;
 mod game;
+pub mod leaderboard;
 mod segments;
+mod settings;
 mod sound;
+mod stats;
 
 #[wasm_bindgen(start)]
 pub fn main_js() -> Result<(), JsValue> {
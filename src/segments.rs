@@ -1,104 +1,950 @@
+use std::collections::{HashSet, VecDeque};
 use std::rc::Rc;
 
-use web_sys::HtmlImageElement;
+use anyhow::{anyhow, Result};
+use rand::rngs::StdRng;
+use rand::Rng;
+use serde::Deserialize;
 
 use crate::{
-  engine::{Image, Point, Rect, SpriteSheet},
-  game::{Barrier, Obstacle, Platform},
+  engine::{Image, ImageElement, Point, Rect, SheetRect, SpriteSheet},
+  game::red_hat_boy_states::{
+    Footing, COYOTE_TICKS, FLOOR, JUMP_SPEED, PLAYER_HEIGHT,
+  },
+  game::{Barrier, Beam, Collectible, CollectibleKind, Obstacle, Platform},
 };
 
-const FIRST_PLATFORM: i16 = 400;
-static FLOATING_PLATFORM_BOUNDING_BOXES: [Rect; 3] = [
-  Rect {
-    position: Point {
-      x: 0,
-      y: 0,
-    },
-    width: 60,
-    height: 54,
+// Covers the hitboxes of every Run and Jump frame in rhb_hitboxes.json
+const BOY_HITBOX_HEIGHT: i16 = 110;
+const BOY_HITBOX_WIDTH: i16 = 58;
+const BOY_HITBOX_X: i16 = 73;
+const BOY_HITBOX_Y: i16 = 17;
+// Top of the highest hitbox of every Slide frame in rhb_hitboxes.json
+const BOY_SLIDING_HITBOX_Y: i16 = 55;
+const COIN_LEAD: i16 = 60;
+const COLLECTIBLE_SIZE: i16 = 32;
+const GEM_PERCENT: u32 = 25;
+const GEM_PLATFORM_HEIGHT: i16 = 150;
+const GENERATED_GAP_MAX: i16 = 300;
+const GENERATED_GAP_MIN: i16 = 100;
+const GENERATED_OBSTACLES_MAX: u32 = 3;
+const GENERATION_ATTEMPTS: u32 = 10;
+const GROUND: i16 = FLOOR + PLAYER_HEIGHT;
+const INITIAL_OBSTACLE_OFFSET: i16 = 150;
+const MAX_REPEATS: usize = 1;
+const MAX_WEIGHT: u32 = 1000;
+const PLATFORM_EDGE_HEIGHT: i16 = 54;
+const PLATFORM_EDGE_WIDTH: i16 = 60;
+const PLATFORM_HEIGHTS: [i16; 2] = [
+  375, 420,
+];
+const PLATFORM_MIDDLE_HEIGHT: i16 = 93;
+const PLATFORM_COIN_HEIGHT: i16 = 60;
+const PLATFORM_MIDDLE_TILES_MAX: u32 = 2;
+const PLATFORM_SPRITE_LEFT: &str = "13.png";
+const PLATFORM_SPRITE_MIDDLE: &str = "14.png";
+const PLATFORM_SPRITE_RIGHT: &str = "15.png";
+const PLATFORM_TILE_WIDTH: i16 = 128;
+const POWER_UP_KINDS: [CollectibleKind; 3] = [
+  CollectibleKind::DoubleJump,
+  CollectibleKind::Magnet,
+  CollectibleKind::Shield,
+];
+const POWER_UP_PERCENT: u32 = 10;
+const WEIGHT: u32 = 1;
+
+#[derive(Clone, Copy, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Difficulty {
+  Easy,
+  Medium,
+  Hard,
+}
+
+#[derive(Clone, Deserialize)]
+struct CollectibleDefinition {
+  #[serde(rename = "type")]
+  kind: CollectibleKind,
+  x: i16,
+  y: i16,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+enum ObstacleDefinition {
+  Beam {
+    #[serde(rename = "boundingBoxes")]
+    bounding_boxes: Vec<SheetRect>,
+    sprites: Vec<String>,
+    x: i16,
+    y: i16,
   },
-  Rect {
-    position: Point {
-      x: 60,
-      y: 0,
-    },
-    width: 384 - (60 * 2),
-    height: 93,
+  Platform {
+    #[serde(rename = "boundingBoxes")]
+    bounding_boxes: Vec<SheetRect>,
+    #[serde(default, rename = "pushBack")]
+    push_back: bool,
+    sprites: Vec<String>,
+    x: i16,
+    y: i16,
   },
-  Rect {
-    position: Point {
-      x: 384 - 60,
-      y: 0,
-    },
-    width: 60,
-    height: 54,
+  Stone {
+    x: i16,
+    y: i16,
   },
-];
-const FLOATING_PLATFORM_SPRITES: [&str; 3] = [
-  "13.png", "14.png", "15.png",
-];
-// const HEIGHT: i16 = 600;
-const LOW_PLATFORM: i16 = 420;
-const HIGH_PLATFORM: i16 = 375;
-const INITIAL_STONE_OFFSET: i16 = 150;
-const STONE_ON_GROUND: i16 = 546;
-
-pub fn platform_and_stone(
-  offset_x: i16,
-  sprite_sheet: Rc<SpriteSheet>,
-  stone: HtmlImageElement, // TODO: use Rc
-) -> Vec<Box<dyn Obstacle>> {
-  vec![
-    Box::new(Barrier::new(Image::new(
-      stone,
-      Point {
-        x: offset_x + INITIAL_STONE_OFFSET,
-        y: STONE_ON_GROUND,
-      },
-    ))),
-    Box::new(create_floating_platform(
-      Point {
-        x: offset_x + FIRST_PLATFORM,
-        y: HIGH_PLATFORM,
-      },
-      sprite_sheet,
-    )),
-  ]
 }
 
-pub fn stone_and_platform(
-  offset_x: i16,
-  sprite_sheet: Rc<SpriteSheet>,
-  stone: HtmlImageElement, // TODO: use Rc
-) -> Vec<Box<dyn Obstacle>> {
-  vec![
-    Box::new(Barrier::new(Image::new(
-      stone,
-      Point {
-        x: offset_x + INITIAL_STONE_OFFSET,
-        y: STONE_ON_GROUND,
-      },
-    ))),
-    Box::new(create_floating_platform(
-      Point {
-        x: offset_x + FIRST_PLATFORM,
-        y: LOW_PLATFORM,
+enum Hazard {
+  Barrier(Rect),
+  Platform {
+    bounding_boxes: Vec<Rect>,
+    top: i16,
+  },
+}
+
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
+struct Reach {
+  footing: Footing,
+  jumping: bool,
+  x: i16,
+}
+
+impl Reach {
+  fn can_jump(&self) -> bool {
+    !self.jumping && self.footing.can_jump()
+  }
+
+  fn hitbox(&self) -> Rect {
+    Rect::new_from_x_y(
+      self.x + BOY_HITBOX_X,
+      self.footing.y + BOY_HITBOX_Y,
+      BOY_HITBOX_WIDTH,
+      BOY_HITBOX_HEIGHT,
+    )
+  }
+
+  fn step(
+    mut self,
+    jump: bool,
+    speed: i16,
+    hazards: &[Hazard],
+  ) -> Option<Reach> {
+    if jump {
+      self.jumping = true;
+      self.footing.velocity_y = JUMP_SPEED;
+    }
+    self.footing = self.footing.step();
+    if self.footing.y >= FLOOR {
+      self.jumping = false;
+    }
+    self.x += speed;
+    for hazard in hazards {
+      let hitbox = self.hitbox();
+      match hazard {
+        Hazard::Barrier(bounding_box) => {
+          if hitbox.intersects(bounding_box) {
+            return None;
+          }
+        },
+        Hazard::Platform {
+          bounding_boxes,
+          top,
+        } => {
+          let landing = bounding_boxes
+            .iter()
+            .find(|bounding_box| hitbox.intersects(bounding_box));
+          if let Some(bounding_box) = landing {
+            if self.footing.velocity_y > 0 && self.footing.y < *top {
+              self.jumping = false;
+              self.footing =
+                self.footing.land(bounding_box.y() - PLAYER_HEIGHT);
+            } else {
+              return None;
+            }
+          }
+        },
+      }
+    }
+    Some(self)
+  }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Segment {
+  #[serde(default)]
+  collectibles: Vec<CollectibleDefinition>,
+  #[serde(default)]
+  pub cooldown: usize,
+  pub difficulty: Difficulty,
+  #[serde(default)]
+  pub min_meters: u32,
+  pub name: String,
+  #[serde(default)]
+  obstacles: Vec<ObstacleDefinition>,
+  #[serde(default)]
+  pub procedural: bool,
+  #[serde(default = "default_weight")]
+  pub weight: u32,
+}
+
+impl Segment {
+  pub fn create(
+    &self,
+    offset_x: i16,
+    sprite_sheet: Rc<SpriteSheet>,
+    stone: ImageElement, // TODO: use Rc
+  ) -> Vec<Box<dyn Obstacle>> {
+    self
+      .obstacles
+      .iter()
+      .map(|obstacle| -> Box<dyn Obstacle> {
+        match obstacle {
+          ObstacleDefinition::Beam {
+            bounding_boxes,
+            sprites,
+            x,
+            y,
+          } => Box::new(create_beam(
+            bounding_boxes,
+            Point {
+              x: offset_x + x,
+              y: *y,
+            },
+            sprite_sheet.clone(),
+            sprites,
+          )),
+          ObstacleDefinition::Platform {
+            bounding_boxes,
+            push_back,
+            sprites,
+            x,
+            y,
+          } => Box::new(create_platform(
+            bounding_boxes,
+            Point {
+              x: offset_x + x,
+              y: *y,
+            },
+            *push_back,
+            sprite_sheet.clone(),
+            sprites,
+          )),
+          ObstacleDefinition::Stone {
+            x,
+            y,
+          } => Box::new(Barrier::new(Image::new(
+            stone.clone(),
+            Point {
+              x: offset_x + x,
+              y: *y,
+            },
+          ))),
+        }
+      })
+      .collect()
+  }
+
+  pub fn create_collectibles(
+    &self,
+    offset_x: i16,
+    sheet: Rc<SpriteSheet>,
+  ) -> Vec<Collectible> {
+    self
+      .collectibles
+      .iter()
+      .map(|collectible| {
+        Collectible::new(
+          collectible.kind,
+          Point {
+            x: offset_x + collectible.x,
+            y: collectible.y,
+          },
+          sheet.clone(),
+        )
+      })
+      .collect()
+  }
+
+  fn hazards(
+    &self,
+    stone: &ImageElement,
+  ) -> Vec<Hazard> {
+    self
+      .obstacles
+      .iter()
+      .map(|obstacle| match obstacle {
+        ObstacleDefinition::Beam {
+          bounding_boxes,
+          x,
+          y,
+          ..
+        } => Hazard::Barrier(
+          to_rects(bounding_boxes)
+            .into_iter()
+            .reduce(|beam, bounding_box| beam.union(&bounding_box))
+            .map(|beam| {
+              Rect::new_from_x_y(
+                x + beam.x(),
+                y + beam.y(),
+                beam.width,
+                beam.height,
+              )
+            })
+            .unwrap_or_default(),
+        ),
+        ObstacleDefinition::Platform {
+          bounding_boxes,
+          x,
+          y,
+          ..
+        } => Hazard::Platform {
+          bounding_boxes: bounding_boxes
+            .iter()
+            .map(|bounding_box| {
+              Rect::new_from_x_y(
+                x + bounding_box.x,
+                y + bounding_box.y,
+                bounding_box.w,
+                bounding_box.h,
+              )
+            })
+            .collect(),
+          top: *y,
+        },
+        ObstacleDefinition::Stone {
+          x,
+          y,
+        } => Hazard::Barrier(Rect::new_from_x_y(
+          *x,
+          *y,
+          stone.width(),
+          stone.height(),
+        )),
+      })
+      .collect()
+  }
+
+  fn is_clearable(
+    &self,
+    stone: &ImageElement,
+    speed: i16,
+  ) -> bool {
+    let hazards = self.hazards(stone);
+    let end = hazards
+      .iter()
+      .map(|hazard| match hazard {
+        Hazard::Barrier(bounding_box) => bounding_box.right(),
+        Hazard::Platform {
+          bounding_boxes,
+          ..
+        } => bounding_boxes
+          .iter()
+          .map(Rect::right)
+          .max()
+          .unwrap_or_default(),
+      })
+      .max()
+      .unwrap_or_default();
+    let start = Reach {
+      footing: Footing {
+        coyote_ticks: COYOTE_TICKS,
+        velocity_y: 0,
+        y: FLOOR,
       },
-      sprite_sheet,
-    )),
-  ]
+      jumping: false,
+      x: -(BOY_HITBOX_X + BOY_HITBOX_WIDTH),
+    };
+    let mut visited = HashSet::new();
+    let mut pending = vec![start];
+    while let Some(reach) = pending.pop() {
+      if reach.hitbox().x() > end {
+        return true;
+      }
+      if !visited.insert(reach) {
+        continue;
+      }
+      pending.extend(reach.step(false, speed, &hazards));
+      if reach.can_jump() {
+        pending.extend(reach.step(true, speed, &hazards));
+      }
+    }
+    false
+  }
+
+  fn validate(
+    &self,
+    sprite_sheet: &SpriteSheet,
+  ) -> Result<()> {
+    if self.weight > MAX_WEIGHT {
+      return Err(anyhow!(
+        "Segment {} has weight {} above {}",
+        self.name,
+        self.weight,
+        MAX_WEIGHT
+      ));
+    }
+    if self.procedural {
+      if !self.obstacles.is_empty() {
+        return Err(anyhow!(
+          "Procedural segment {} cannot list obstacles",
+          self.name
+        ));
+      }
+      return Ok(());
+    }
+    if self.obstacles.is_empty() {
+      return Err(anyhow!("Segment {} has no obstacles", self.name));
+    }
+    self
+      .obstacles
+      .iter()
+      .try_for_each(|obstacle| match obstacle {
+        ObstacleDefinition::Beam {
+          bounding_boxes,
+          sprites,
+          y,
+          ..
+        } => {
+          self.validate_sprites(
+            "Beam",
+            bounding_boxes,
+            sprite_sheet,
+            sprites,
+          )?;
+          self.validate_beam_height(bounding_boxes, *y)
+        },
+        ObstacleDefinition::Platform {
+          bounding_boxes,
+          sprites,
+          ..
+        } => self.validate_sprites(
+          "Platform",
+          bounding_boxes,
+          sprite_sheet,
+          sprites,
+        ),
+        ObstacleDefinition::Stone {
+          ..
+        } => Ok(()),
+      })
+  }
+
+  fn validate_beam_height(
+    &self,
+    bounding_boxes: &[SheetRect],
+    y: i16,
+  ) -> Result<()> {
+    let bottom = bounding_boxes
+      .iter()
+      .map(|bounding_box| y + bounding_box.y + bounding_box.h)
+      .max()
+      .unwrap_or(y);
+    let standing_top = FLOOR + BOY_HITBOX_Y;
+    let sliding_top = FLOOR + BOY_SLIDING_HITBOX_Y;
+    if bottom <= standing_top || bottom > sliding_top {
+      return Err(anyhow!(
+        "Beam in segment {} has its bottom at {} but it must be below {} and at most {}",
+        self.name,
+        bottom,
+        standing_top,
+        sliding_top
+      ));
+    }
+    Ok(())
+  }
+
+  fn validate_sprites(
+    &self,
+    kind: &str,
+    bounding_boxes: &[SheetRect],
+    sprite_sheet: &SpriteSheet,
+    sprites: &[String],
+  ) -> Result<()> {
+    if bounding_boxes.is_empty() || sprites.is_empty() {
+      return Err(anyhow!(
+        "{} in segment {} needs sprites and bounding boxes",
+        kind,
+        self.name
+      ));
+    }
+    sprites.iter().try_for_each(|sprite| {
+      sprite_sheet.cell(sprite).map(|_cell| ()).ok_or_else(|| {
+        anyhow!("Segment {} uses unknown sprite {}", self.name, sprite)
+      })
+    })
+  }
+}
+
+#[derive(Default)]
+pub struct SegmentHistory {
+  recent: VecDeque<usize>,
+}
+
+impl SegmentHistory {
+  fn allows(
+    &self,
+    index: usize,
+    cooldown: usize,
+    max_repeats: usize,
+  ) -> bool {
+    let cooled_down = !self
+      .recent
+      .iter()
+      .take(cooldown)
+      .any(|recent| *recent == index);
+    let repeats = self
+      .recent
+      .iter()
+      .take_while(|recent| **recent == index)
+      .count();
+    cooled_down && repeats < max_repeats
+  }
+
+  fn record(
+    &mut self,
+    index: usize,
+    length: usize,
+  ) {
+    self.recent.push_front(index);
+    self.recent.truncate(length);
+  }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentRegistry {
+  #[serde(default = "default_max_repeats")]
+  pub max_repeats: usize,
+  pub segments: Vec<Segment>,
+  pub start: String,
+}
+
+impl SegmentRegistry {
+  pub fn choose(
+    &self,
+    history: &SegmentHistory,
+    meters: u32,
+    rng: &mut StdRng,
+    weight_percent: impl Fn(Difficulty) -> u32,
+  ) -> Option<usize> {
+    let weights: Vec<u32> = self
+      .segments
+      .iter()
+      .enumerate()
+      .map(|(index, segment)| {
+        if meters >= segment.min_meters
+          && history.allows(index, segment.cooldown, self.max_repeats)
+        {
+          segment.weight * weight_percent(segment.difficulty)
+        } else {
+          0
+        }
+      })
+      .collect();
+    let total: u32 = weights.iter().sum();
+    (total > 0)
+      .then(|| {
+        let mut roll = rng.gen_range(0..total);
+        weights.iter().position(|weight| {
+          if roll < *weight {
+            return true;
+          }
+          roll -= weight;
+          false
+        })
+      })
+      .flatten()
+  }
+
+  fn history_length(&self) -> usize {
+    self
+      .segments
+      .iter()
+      .map(|segment| segment.cooldown)
+      .max()
+      .unwrap_or_default()
+      .max(self.max_repeats)
+  }
+
+  pub fn record(
+    &self,
+    history: &mut SegmentHistory,
+    index: usize,
+  ) {
+    history.record(index, self.history_length());
+  }
+
+  pub fn start(
+    &self,
+    history: &mut SegmentHistory,
+  ) -> &Segment {
+    let index = self.start_index();
+    self.record(history, index);
+    &self.segments[index]
+  }
+
+  pub fn start_segment(&self) -> &Segment {
+    &self.segments[self.start_index()]
+  }
+
+  fn start_index(&self) -> usize {
+    self
+      .segments
+      .iter()
+      .position(|segment| segment.name == self.start)
+      .expect("Start segment not found")
+  }
+
+  pub fn validate(
+    &self,
+    sprite_sheet: &SpriteSheet,
+  ) -> Result<()> {
+    let start = self
+      .segments
+      .iter()
+      .find(|segment| segment.name == self.start)
+      .ok_or_else(|| anyhow!("Start segment {} is not defined", self.start))?;
+    if start.procedural {
+      return Err(anyhow!("Start segment {} is procedural", self.start));
+    }
+    if self.max_repeats == 0 {
+      return Err(anyhow!("maxRepeats must be at least 1"));
+    }
+    let mut names = HashSet::new();
+    self.segments.iter().try_for_each(|segment| {
+      if !names.insert(segment.name.as_str()) {
+        return Err(anyhow!("Segment {} is defined twice", segment.name));
+      }
+      segment.validate(sprite_sheet)
+    })
+  }
+}
+
+pub fn generate(
+  template: &Segment,
+  rng: &mut StdRng,
+  stone: &ImageElement,
+  speed: i16,
+) -> Option<Segment> {
+  (0..GENERATION_ATTEMPTS)
+    .map(|_attempt| generate_layout(template, rng, stone))
+    .find(|segment| segment.is_clearable(stone, speed))
 }
 
 // private functions
 
-fn create_floating_platform(
+fn default_max_repeats() -> usize {
+  MAX_REPEATS
+}
+
+fn default_weight() -> u32 {
+  WEIGHT
+}
+
+fn create_beam(
+  bounding_boxes: &[SheetRect],
   position: Point,
   sprite_sheet: Rc<SpriteSheet>,
+  sprites: &[String],
+) -> Beam {
+  let sprite_names: Vec<&str> = sprites.iter().map(String::as_str).collect();
+  Beam::new(
+    &to_rects(bounding_boxes),
+    position,
+    sprite_sheet,
+    &sprite_names,
+  )
+}
+
+fn create_platform(
+  bounding_boxes: &[SheetRect],
+  position: Point,
+  push_back: bool,
+  sprite_sheet: Rc<SpriteSheet>,
+  sprites: &[String],
 ) -> Platform {
+  let sprite_names: Vec<&str> = sprites.iter().map(String::as_str).collect();
   Platform::new(
-    &FLOATING_PLATFORM_BOUNDING_BOXES,
+    &to_rects(bounding_boxes),
     position,
+    push_back,
     sprite_sheet,
-    &FLOATING_PLATFORM_SPRITES,
+    &sprite_names,
   )
 }
+
+fn generate_layout(
+  template: &Segment,
+  rng: &mut StdRng,
+  stone: &ImageElement,
+) -> Segment {
+  let count = rng.gen_range(1..=GENERATED_OBSTACLES_MAX);
+  let mut x = INITIAL_OBSTACLE_OFFSET;
+  let mut collectibles = Vec::new();
+  let obstacles = (0..count)
+    .map(|index| {
+      let kind = if index == 0 && rng.gen_range(0..100) < POWER_UP_PERCENT {
+        POWER_UP_KINDS[rng.gen_range(0..POWER_UP_KINDS.len() as u32) as usize]
+      } else {
+        CollectibleKind::Coin
+      };
+      collectibles.push(CollectibleDefinition {
+        kind,
+        x: x - COIN_LEAD,
+        y: GROUND - COLLECTIBLE_SIZE * 2,
+      });
+      let obstacle = if rng.gen_range(0..2u32) == 0 {
+        let stone_x = x;
+        x += stone.width();
+        ObstacleDefinition::Stone {
+          x: stone_x,
+          y: GROUND - stone.height(),
+        }
+      } else {
+        let middle_tiles = rng.gen_range(1..=PLATFORM_MIDDLE_TILES_MAX);
+        let y = PLATFORM_HEIGHTS[rng.gen_range(0..2u32) as usize];
+        let platform = platform_definition(x, y, middle_tiles);
+        let width = PLATFORM_TILE_WIDTH * (middle_tiles as i16 + 2);
+        collectibles.push(if rng.gen_range(0..100) < GEM_PERCENT {
+          CollectibleDefinition {
+            kind: CollectibleKind::Gem,
+            x: x + (width - COLLECTIBLE_SIZE) / 2,
+            y: y - GEM_PLATFORM_HEIGHT,
+          }
+        } else {
+          CollectibleDefinition {
+            kind: CollectibleKind::Coin,
+            x: x + (width - COLLECTIBLE_SIZE) / 2,
+            y: y - PLATFORM_COIN_HEIGHT,
+          }
+        });
+        x += width;
+        platform
+      };
+      x += rng.gen_range(GENERATED_GAP_MIN..=GENERATED_GAP_MAX);
+      obstacle
+    })
+    .collect();
+  Segment {
+    collectibles,
+    obstacles,
+    procedural: false,
+    ..template.clone()
+  }
+}
+
+fn platform_definition(
+  x: i16,
+  y: i16,
+  middle_tiles: u32,
+) -> ObstacleDefinition {
+  let width = PLATFORM_TILE_WIDTH * (middle_tiles as i16 + 2);
+  let mut sprites = vec![PLATFORM_SPRITE_LEFT.to_string()];
+  sprites.extend((0..middle_tiles).map(|_tile| PLATFORM_SPRITE_MIDDLE.into()));
+  sprites.push(PLATFORM_SPRITE_RIGHT.into());
+  ObstacleDefinition::Platform {
+    bounding_boxes: vec![
+      SheetRect {
+        x: 0,
+        y: 0,
+        w: PLATFORM_EDGE_WIDTH,
+        h: PLATFORM_EDGE_HEIGHT,
+      },
+      SheetRect {
+        x: PLATFORM_EDGE_WIDTH,
+        y: 0,
+        w: width - PLATFORM_EDGE_WIDTH * 2,
+        h: PLATFORM_MIDDLE_HEIGHT,
+      },
+      SheetRect {
+        x: width - PLATFORM_EDGE_WIDTH,
+        y: 0,
+        w: PLATFORM_EDGE_WIDTH,
+        h: PLATFORM_EDGE_HEIGHT,
+      },
+    ],
+    push_back: false,
+    sprites,
+    x,
+    y,
+  }
+}
+
+fn to_rects(bounding_boxes: &[SheetRect]) -> Vec<Rect> {
+  bounding_boxes
+    .iter()
+    .map(|bounding_box| {
+      Rect::new_from_x_y(
+        bounding_box.x,
+        bounding_box.y,
+        bounding_box.w,
+        bounding_box.h,
+      )
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::game::red_hat_boy_states::RUNNING_SPEED;
+  use crate::game::tests::assets;
+  use rand::SeedableRng;
+
+  const ROLLS: usize = 4000;
+  const SEED: u64 = 42;
+
+  fn counts(
+    registry: &SegmentRegistry,
+    history: &SegmentHistory,
+    meters: u32,
+  ) -> Vec<usize> {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let mut counts = vec![0; registry.segments.len()];
+    for _roll in 0..ROLLS {
+      if let Some(index) =
+        registry.choose(history, meters, &mut rng, |_tag| 100)
+      {
+        counts[index] += 1;
+      }
+    }
+    counts
+  }
+
+  fn registry(segments: &str) -> SegmentRegistry {
+    let json = format!(
+      r#"{{"maxRepeats":5,"start":"start","segments":[{}]}}"#,
+      segments
+    );
+    serde_json::from_str(&json).expect("Could not parse registry")
+  }
+
+  fn segment(obstacles: &str) -> Segment {
+    let json = format!(
+      r#"{{"name":"test","difficulty":"easy","obstacles":[{}]}}"#,
+      obstacles
+    );
+    serde_json::from_str(&json).expect("Could not parse segment")
+  }
+
+  #[test]
+  fn choose_follows_the_weights() {
+    let registry = registry(
+      r#"{"name":"start","difficulty":"easy"},
+      {"name":"heavy","difficulty":"easy","weight":3}"#,
+    );
+    let counts = counts(&registry, &SegmentHistory::default(), 0);
+    assert_eq!(counts.iter().sum::<usize>(), ROLLS);
+    assert!((2800..3200).contains(&counts[1]), "{:?}", counts);
+  }
+
+  #[test]
+  fn choose_never_picks_a_segment_below_its_min_meters() {
+    let registry = registry(
+      r#"{"name":"start","difficulty":"easy"},
+      {"name":"far","difficulty":"easy","minMeters":100}"#,
+    );
+    let history = SegmentHistory::default();
+    assert_eq!(counts(&registry, &history, 99)[1], 0);
+    assert!(counts(&registry, &history, 100)[1] > 0);
+  }
+
+  #[test]
+  fn choose_never_picks_a_segment_on_cooldown() {
+    let registry = registry(
+      r#"{"name":"start","difficulty":"easy"},
+      {"name":"cooling","difficulty":"easy","cooldown":2},
+      {"name":"other","difficulty":"easy"}"#,
+    );
+    let mut history = SegmentHistory::default();
+    registry.record(&mut history, 1);
+    assert_eq!(counts(&registry, &history, 0)[1], 0);
+    registry.record(&mut history, 2);
+    assert_eq!(counts(&registry, &history, 0)[1], 0);
+    registry.record(&mut history, 0);
+    assert!(counts(&registry, &history, 0)[1] > 0);
+  }
+
+  #[test]
+  fn generate_returns_only_clearable_segments() {
+    let assets = assets();
+    let template = assets
+      .segments
+      .segments
+      .iter()
+      .find(|segment| segment.procedural)
+      .expect("No procedural segment");
+    let mut rng = StdRng::seed_from_u64(SEED);
+    for speed in [
+      RUNNING_SPEED,
+      RUNNING_SPEED * 2,
+    ] {
+      for _segment in 0..100 {
+        let generated = generate(template, &mut rng, &assets.stone, speed)
+          .expect("No clearable layout");
+        assert!(!generated.obstacles.is_empty());
+        assert!(generated.is_clearable(&assets.stone, speed));
+      }
+    }
+  }
+
+  #[test]
+  fn is_clearable_rejects_a_wall_taller_than_a_jump() {
+    let wall = segment(
+      r#"{"type":"platform","x":400,"y":0,"sprites":["14.png"],
+      "boundingBoxes":[{"x":0,"y":0,"w":128,"h":600}]}"#,
+    );
+    assert!(!wall.is_clearable(&assets().stone, RUNNING_SPEED));
+  }
+
+  #[test]
+  fn is_clearable_treats_a_beam_as_impassable() {
+    let stone = assets().stone;
+    let beam = segment(
+      r#"{"type":"beam","x":200,"y":-112,"sprites":["5.png"],
+      "boundingBoxes":[{"x":4,"y":0,"w":120,"h":632}]}"#,
+    );
+    assert!(!beam.is_clearable(&stone, RUNNING_SPEED));
+    let jumpable = segment(r#"{"type":"stone","x":200,"y":546}"#);
+    assert!(jumpable.is_clearable(&stone, RUNNING_SPEED));
+  }
+
+  #[test]
+  fn validate_accepts_the_bundled_beam() {
+    let assets = assets();
+    let beam_and_stone = assets
+      .segments
+      .segments
+      .iter()
+      .find(|segment| segment.name == "beam_and_stone")
+      .expect("No beam_and_stone segment");
+    assert!(beam_and_stone.validate(&assets.tiles).is_ok());
+  }
+
+  #[test]
+  fn validate_rejects_a_beam_that_cannot_be_slid_under() {
+    let tiles = assets().tiles;
+    let beam = |y: i16| {
+      segment(&format!(
+        r#"{{"type":"beam","x":200,"y":{},"sprites":["5.png"],
+        "boundingBoxes":[{{"x":4,"y":0,"w":120,"h":632}}]}}"#,
+        y
+      ))
+    };
+    assert!(beam(-135).validate(&tiles).is_ok());
+    assert!(beam(-98).validate(&tiles).is_ok());
+    assert!(beam(-136).validate(&tiles).is_err());
+    assert!(beam(-97).validate(&tiles).is_err());
+  }
+
+  #[test]
+  fn validate_rejects_a_weight_above_the_maximum() {
+    let tiles = assets().tiles;
+    let mut heavy = segment(r#"{"type":"stone","x":200,"y":546}"#);
+    heavy.weight = MAX_WEIGHT;
+    assert!(heavy.validate(&tiles).is_ok());
+    heavy.weight = MAX_WEIGHT + 1;
+    assert!(heavy.validate(&tiles).is_err());
+  }
+}
@@ -0,0 +1,61 @@
+use crate::browser::storage;
+use crate::controls::Bindings;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_LIVES: u8 = 3;
+const DEFAULT_VOLUME: f32 = 0.8;
+// Key bindings were stored on their own before they moved into the settings
+const LEGACY_BINDINGS_KEY: &str = "walk-the-dog.bindings";
+pub const MAX_LIVES: u8 = 5;
+const SETTINGS_KEY: &str = "walk-the-dog.settings";
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Settings {
+  pub bindings: Bindings,
+  pub lives: u8,
+  pub player_name: String,
+  pub volume: f32,
+}
+
+impl Default for Settings {
+  fn default() -> Self {
+    Settings {
+      bindings: Bindings::default(),
+      lives: DEFAULT_LIVES,
+      player_name: String::new(),
+      volume: DEFAULT_VOLUME,
+    }
+  }
+}
+
+impl Settings {
+  pub fn load() -> Self {
+    let mut settings = match storage::load(SETTINGS_KEY) {
+      Ok(Some(settings)) => settings,
+      Ok(None) => Settings::default(),
+      Err(err) => {
+        log!("Error loading settings {:#?}", err);
+        Settings::default()
+      },
+    };
+    if let Err(err) = settings.migrate_bindings() {
+      log!("Error migrating key bindings {:#?}", err);
+    }
+    settings
+  }
+
+  fn migrate_bindings(&mut self) -> Result<()> {
+    let Some(bindings) = storage::load(LEGACY_BINDINGS_KEY)? else {
+      return Ok(());
+    };
+    self.bindings = bindings;
+    self.save()?;
+    storage::remove(LEGACY_BINDINGS_KEY)
+  }
+
+  pub fn save(&self) -> Result<()> {
+    storage::save(SETTINGS_KEY, self)
+  }
+}
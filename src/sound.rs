@@ -3,19 +3,16 @@ use js_sys::ArrayBuffer;
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{
-  AudioBuffer, AudioBufferSourceNode, AudioContext, AudioDestinationNode,
-  AudioNode,
+  AudioBuffer, AudioBufferSourceNode, AudioContext, AudioNode, GainNode,
 };
 
 fn connect_with_audio_node(
-  buffer_source: &AudioBufferSourceNode,
-  destination: &AudioDestinationNode,
+  source: &AudioNode,
+  destination: &AudioNode,
 ) -> Result<AudioNode> {
-  buffer_source
-    .connect_with_audio_node(destination)
-    .map_err(|err| {
-      anyhow!("Error connecting audio source to destination {:#?}", err)
-    })
+  source.connect_with_audio_node(destination).map_err(|err| {
+    anyhow!("Error connecting audio source to destination {:#?}", err)
+  })
 }
 
 pub fn create_audio_context() -> Result<AudioContext> {
@@ -23,6 +20,14 @@ pub fn create_audio_context() -> Result<AudioContext> {
     .map_err(|err| anyhow!("Could not create audio context: {:#?}", err))
 }
 
+pub fn create_gain_node(ctx: &AudioContext) -> Result<GainNode> {
+  let gain_node = ctx
+    .create_gain()
+    .map_err(|err| anyhow!("Error creating gain node {:#?}", err))?;
+  connect_with_audio_node(&gain_node, &ctx.destination())?;
+  Ok(gain_node)
+}
+
 fn create_buffer_source(ctx: &AudioContext) -> Result<AudioBufferSourceNode> {
   ctx
     .create_buffer_source()
@@ -32,10 +37,11 @@ fn create_buffer_source(ctx: &AudioContext) -> Result<AudioBufferSourceNode> {
 fn create_track_source(
   ctx: &AudioContext,
   buffer: &AudioBuffer,
+  destination: &AudioNode,
 ) -> Result<AudioBufferSourceNode> {
   let track_source = create_buffer_source(ctx)?;
   track_source.set_buffer(Some(buffer));
-  connect_with_audio_node(&track_source, &ctx.destination())?;
+  connect_with_audio_node(&track_source, destination)?;
   Ok(track_source)
 }
 
@@ -60,9 +66,11 @@ pub enum Looping {
 pub fn play_sound(
   ctx: &AudioContext,
   buffer: &AudioBuffer,
+  destination: &AudioNode,
   looping: Looping,
 ) -> Result<()> {
-  let track_source: AudioBufferSourceNode = create_track_source(ctx, buffer)?;
+  let track_source: AudioBufferSourceNode =
+    create_track_source(ctx, buffer, destination)?;
   if matches!(looping, Looping::Yes) {
     track_source.set_loop(true);
   }
@@ -72,3 +80,24 @@ pub fn play_sound(
     .start()
     .map_err(|err| anyhow!("Could not start sound! {:#?}", err))
 }
+
+pub fn resume_audio_context(ctx: &AudioContext) -> Result<()> {
+  ctx
+    .resume()
+    .map(|_promise| ())
+    .map_err(|err| anyhow!("Could not resume audio context {:#?}", err))
+}
+
+pub fn suspend_audio_context(ctx: &AudioContext) -> Result<()> {
+  ctx
+    .suspend()
+    .map(|_promise| ())
+    .map_err(|err| anyhow!("Could not suspend audio context {:#?}", err))
+}
+
+pub fn set_gain(
+  gain_node: &GainNode,
+  value: f32,
+) {
+  gain_node.gain().set_value(value);
+}
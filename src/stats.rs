@@ -0,0 +1,48 @@
+use crate::browser::storage;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+const BEST_DISTANCES: usize = 5;
+const STATS_KEY: &str = "walk-the-dog.stats";
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stats {
+  best_distances: Vec<u32>,
+  total_runs: u32,
+}
+
+impl Stats {
+  pub fn best_distances(&self) -> &[u32] {
+    &self.best_distances
+  }
+
+  pub fn load() -> Self {
+    match storage::load(STATS_KEY) {
+      Ok(Some(stats)) => stats,
+      Ok(None) => Stats::default(),
+      Err(err) => {
+        log!("Error loading stats {:#?}", err);
+        Stats::default()
+      },
+    }
+  }
+
+  pub fn record_run(
+    &mut self,
+    distance: u32,
+  ) {
+    self.total_runs += 1;
+    self.best_distances.push(distance);
+    self.best_distances.sort_unstable_by(|a, b| b.cmp(a));
+    self.best_distances.truncate(BEST_DISTANCES);
+  }
+
+  pub fn save(&self) -> Result<()> {
+    storage::save(STATS_KEY, self)
+  }
+
+  pub fn total_runs(&self) -> u32 {
+    self.total_runs
+  }
+}